@@ -0,0 +1,96 @@
+//! Measures the round trip of `RconClient::exec` against a local echo server and compares
+//! it with sending the command and the control packet field by field, as `serialize` did
+//! before packets were encoded into one buffer.
+//!
+//! Run with `cargo bench --bench exec_latency`.
+
+use facio::{client::*, ll::*, raw_packet::*};
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 200;
+
+// Answers the auth request with the double reply, commands with their own body and
+// mirrors the empty `ResponseValue` control packet.
+fn spawn_echo_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            // the server answers every packet right away; only the client side is measured.
+            stream.set_nodelay(true).unwrap();
+            thread::spawn(move || {
+                let auth = recv_packet(&mut stream).unwrap();
+                send_packets(&mut stream,
+                             &[RawPacket::new_response_value(auth.pid, "").unwrap(),
+                               RawPacket::new_response_auth(auth.pid, "").unwrap()]).unwrap();
+
+                while let Ok(packet) = recv_packet(&mut stream) {
+                    let body = {
+                        if packet.request_type() == Some(PacketType::RequestExecCommand) {
+                            packet.pbody
+                        } else {
+                            Vec::new()
+                        }
+                    };
+                    send_packet(&mut stream,
+                                &RawPacket::new_response_value(packet.pid, body).unwrap()).unwrap();
+                }
+            });
+        }
+    });
+
+    addr
+}
+
+// The former `serialize`: one write per field, straight to the socket.
+fn serialize_per_field(packet: &RawPacket, stream: &mut TcpStream) {
+    stream.write_all(&(packet.pbody.len() as i32 + 10).to_le_bytes()).unwrap();
+    stream.write_all(&packet.pid.to_le_bytes()).unwrap();
+    stream.write_all(&packet.ptype.to_le_bytes()).unwrap();
+    stream.write_all(&packet.pbody).unwrap();
+    stream.write_all(&[0]).unwrap();
+    stream.write_all(&[0]).unwrap();
+    stream.flush().unwrap();
+}
+
+fn bench_per_field(addr: &str) -> Duration {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    send_packet(&mut stream, &RawPacket::new_auth(0, "pass").unwrap()).unwrap();
+    recv_packet(&mut stream).unwrap();
+    recv_packet(&mut stream).unwrap();
+
+    let command = RawPacket::new_exec(0, "status").unwrap();
+    let control = RawPacket::new_response_value(-1, "").unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        serialize_per_field(&command, &mut stream);
+        serialize_per_field(&control, &mut stream);
+        while recv_packet(&mut stream).unwrap().pid != control.pid {}
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn bench_exec(addr: &str) -> Duration {
+    let mut rcon =
+        RconClient::open(addr, "pass", None::<String>, None).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        rcon.exec("status").unwrap();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let addr = spawn_echo_server();
+
+    println!("per-field writes:  {:?} per exec", bench_per_field(&addr));
+    println!("coalesced writes:  {:?} per exec", bench_exec(&addr));
+}
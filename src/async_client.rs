@@ -0,0 +1,471 @@
+//! # Async Client
+//!
+//! The async counterpart to the [`client`](../client/index.html) module, built on
+//! [`tokio`](https://docs.rs/tokio)'s `TcpStream`. It is only available with the `tokio`
+//! feature enabled.
+//!
+//! The protocol handling is exactly the one of [`RconClient`](../client/struct.RconClient.html),
+//! as both are built on [`ClientSession`](../session/struct.ClientSession.html): every command is followed by a control packet to detect the end of a multi-packet response
+//! and authentication accepts both the single and the double auth reply of servers. See the
+//! `client` module documentation for the details.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use facio::async_client::*;
+//!
+//! async fn run() -> Result<(), facio::error::RconError> {
+//!    let mut rcon =
+//!        AsyncRconClient::open("127.0.0.1:38742",
+//!                              "mypass",
+//!                              Some("echo"),
+//!                              None).await?;
+//!
+//!    let help = rcon.exec("/help").await?;
+//!    println!("/help from server:\n{}", help);
+//!
+//!    Ok(())
+//! }
+//! # fn main() {}
+//! ```
+
+use super::session::*;
+use super::raw_packet::TextDecoding;
+use super::error::RconError;
+use super::termination::TerminationStrategy;
+use super::probe::{Probe, ServerProfile};
+use super::dialect::Dialect;
+use super::reconnect::ReconnectPolicy;
+use super::client::check_timeout;
+
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// The read and write timeouts of a client.
+#[derive(Clone, Copy, Default)]
+struct Timeouts {
+    read: Option<Duration>,
+    write: Option<Duration>,
+}
+
+// Awaits `io`, for at most `timeout` if given; a timeout is an error of kind `TimedOut`.
+async fn within<T, F>(timeout: Option<Duration>, io: F) -> io::Result<T>
+    where F: std::future::Future<Output = io::Result<T>> {
+    match timeout {
+        Some(timeout) =>
+            tokio::time::timeout(timeout, io).await
+                .unwrap_or_else(|_| Err(Error::new(ErrorKind::TimedOut, "Timed out."))),
+        None =>
+            io.await,
+    }
+}
+
+// Writes everything the session queued to the stream.
+async fn flush_session(stream: &mut TcpStream,
+                       session: &mut ClientSession,
+                       timeouts: Timeouts) -> io::Result<()> {
+    let outgoing = session.take_outgoing();
+    within(timeouts.write, stream.write_all(&outgoing)).await
+}
+
+// Reads from the stream and feeds the session until it has an event. Whatever the session
+// queues meanwhile is written before reading. See the `next_event` of the blocking client
+// for the timeouts.
+async fn next_event(stream: &mut TcpStream,
+                    session: &mut ClientSession,
+                    timeouts: Timeouts,
+                    deadline: Option<Instant>) -> Result<SessionEvent, RconError> {
+    let mut buffer = [0u8; 4096];
+    loop {
+        if let Some(event) = session.poll_event() {
+            return Ok(event);
+        }
+
+        flush_session(stream, session, timeouts).await?;
+
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let timeout = timeouts.read.into_iter().chain(remaining).min();
+        let read = match session.quiet_period() {
+            Some(period) if timeout.is_none_or(|timeout| period <= timeout) =>
+                match tokio::time::timeout(period, stream.read(&mut buffer)).await {
+                    Ok(read) => read?,
+                    Err(_) => {
+                        session.quiet_period_elapsed();
+                        continue;
+                    },
+                },
+            _ =>
+                within(timeout, stream.read(&mut buffer)).await
+                    .map_err(|e| match e.kind() {
+                        ErrorKind::TimedOut => RconError::Timeout,
+                        _ => e.into(),
+                    })?,
+        };
+        if read == 0 {
+            return Err(
+                Error::new(ErrorKind::UnexpectedEof, "Connection closed by server.").into());
+        }
+        session.feed(&buffer[..read])?;
+    }
+}
+
+// Whether `e` means the connection is lost, rather than e.g. the server rejecting a command.
+fn is_connection_lost(e: &RconError) -> bool {
+    match e {
+        RconError::Io(e) => e.kind() != ErrorKind::TimedOut,
+        RconError::Protocol(_) => true,
+        _ => false,
+    }
+}
+
+// Connects to `addr`, within `timeout` if given.
+async fn connect(addr: SocketAddr, timeout: Option<Duration>) -> Result<TcpStream, RconError> {
+    if let Some(dur) = timeout {
+        Ok(tokio::time::timeout(dur, TcpStream::connect(addr)).await
+            .map_err(|_| RconError::Timeout)??)
+    } else {
+        Ok(TcpStream::connect(addr).await?)
+    }
+}
+
+// Authenticates `session` with `pass` on `stream`. See `next_event` for the timeouts.
+async fn handshake(stream: &mut TcpStream,
+                   session: &mut ClientSession,
+                   pass: &str,
+                   timeouts: Timeouts,
+                   deadline: Option<Instant>) -> Result<(), RconError> {
+    // building auth package:
+    session.authenticate(pass)?;
+
+    // sending auth
+    flush_session(stream, session, timeouts).await?;
+    // ... and recv result:
+    match next_event(stream, session, timeouts, deadline).await? {
+        SessionEvent::AuthOk => Ok(()),
+        _ => Err(RconError::AuthFailed),
+    }
+}
+
+/// The async version of [`RconClient`](../client/struct.RconClient.html).
+///
+/// Like `RconClient` it must not be used by more than one task at a time; `exec`
+/// takes `&mut self` to ensure this.
+///
+/// # Example
+///
+/// ```
+/// use facio::{async_client::*, raw_packet::*};
+/// use std::net::TcpListener;
+///
+/// // a minimal server on a thread, accepting any password and answering "pong".
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap().to_string();
+/// std::thread::spawn(move || {
+///     let (mut stream, _) = listener.accept().unwrap();
+///     while let Ok(packet) = RawPacket::deserialize(&mut stream) {
+///         let reply = if packet.request_type() == Some(PacketType::RequestAuth) {
+///             RawPacket::new_response_auth(packet.pid, "")
+///         } else {
+///             RawPacket::new_response_value(packet.pid, "pong")
+///         };
+///         reply.unwrap().serialize(&mut stream).unwrap();
+///     }
+/// });
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// runtime.block_on(async {
+///     let mut rcon = AsyncRconClient::open(addr, "mypass", Some("echo"), None).await.unwrap();
+///     assert_eq!(rcon.exec("ping").await.unwrap(), "pong");
+/// });
+/// ```
+pub struct AsyncRconClient {
+    open_stream: TcpStream,
+    addr: SocketAddr,
+    session: ClientSession,
+    password: String,
+    text_decoding: TextDecoding,
+    timeouts: Timeouts,
+    broken: bool,
+    reconnect: Option<ReconnectPolicy>,
+    reconnects: u64,
+}
+
+impl AsyncRconClient {
+    /// Submits a command to the open RCON stream and awaits the full response. See
+    /// [`RconClient::exec`](../client/struct.RconClient.html#method.exec).
+    pub async fn exec<T: Into<String>>(&mut self, command: T) -> Result<String, RconError> {
+        self.exec_until(command, None).await
+    }
+
+    /// Like [`exec`](#method.exec), but fails with `RconError::Timeout` if the full response
+    /// did not arrive within `timeout`. See
+    /// [`RconClient::exec_with_timeout`](../client/struct.RconClient.html#method.exec_with_timeout).
+    pub async fn exec_with_timeout<T: Into<String>>(&mut self,
+                                                    command: T,
+                                                    timeout: Duration) -> Result<String, RconError> {
+        self.exec_until(command, Some(Instant::now() + timeout)).await
+    }
+
+    /// Executes all `commands` in one go and returns their responses in order. See
+    /// [`RconClient::exec_many`](../client/struct.RconClient.html#method.exec_many).
+    pub async fn exec_many<T: AsRef<str>>(&mut self, commands: &[T]) -> Result<Vec<Result<String, RconError>>, RconError> {
+        let commands: Vec<String> = commands.iter().map(|command| command.as_ref().to_string()).collect();
+        self.exec_batch(&commands, None).await
+    }
+
+    async fn exec_until<T: Into<String>>(&mut self,
+                                         command: T,
+                                         deadline: Option<Instant>) -> Result<String, RconError> {
+        let mut responses = self.exec_batch(&[command.into()], deadline).await?;
+        responses.pop().expect("a response per command")
+    }
+
+    async fn exec_batch(&mut self,
+                        commands: &[String],
+                        deadline: Option<Instant>) -> Result<Vec<Result<String, RconError>>, RconError> {
+        if self.broken {
+            if self.reconnect.is_none() {
+                return Err(RconError::Broken);
+            }
+            self.reconnect().await?;
+        }
+
+        match self.exec_once(commands, deadline).await {
+            Err(e) if self.reconnect.is_some() && is_connection_lost(&e) => {
+                self.broken = true;
+                let retry = self.reconnect.as_ref().is_some_and(
+                    |policy| commands.iter().all(|command| policy.should_retry(command)));
+                if !retry {
+                    // the next command reconnects.
+                    return Err(e);
+                }
+                self.reconnect().await?;
+                self.exec_once(commands, deadline).await
+            },
+            result => result,
+        }
+    }
+
+    async fn exec_once(&mut self,
+                       commands: &[String],
+                       deadline: Option<Instant>) -> Result<Vec<Result<String, RconError>>, RconError> {
+        let command_ids =
+            self.session.exec_many(commands)?;
+        match self.receive_responses(&command_ids, deadline).await {
+            Err(RconError::Timeout) => {
+                // late packets of the commands are skipped as stale.
+                self.session.cancel();
+                Err(RconError::Timeout)
+            },
+            Err(RconError::Io(e)) if e.kind() == ErrorKind::TimedOut => {
+                self.broken = true;
+                Err(RconError::Timeout)
+            },
+            result => result,
+        }
+    }
+
+    async fn receive_responses(&mut self,
+                               command_ids: &[i32],
+                               deadline: Option<Instant>) -> Result<Vec<Result<String, RconError>>, RconError> {
+        flush_session(&mut self.open_stream, &mut self.session, self.timeouts).await?;
+
+        let mut responses: Vec<Option<Result<String, RconError>>> = command_ids.iter().map(|_| None).collect();
+        while responses.iter().any(Option::is_none) {
+            let (id, response) = match next_event(&mut self.open_stream, &mut self.session, self.timeouts, deadline).await? {
+                SessionEvent::ResponseComplete { id, body } =>
+                    (id, self.text_decoding.decode(body).map_err(RconError::Encoding)),
+                SessionEvent::ResponseTooLarge { id } =>
+                    (id, Err(RconError::ResponseTooLarge)),
+                _ => continue,
+            };
+            if let Some(index) = command_ids.iter().position(|&command_id| command_id == id) {
+                responses[index] = Some(response);
+            }
+        }
+        Ok(responses.into_iter().flatten().collect())
+    }
+
+    // Opens a new connection and authenticates on it, as often as the policy allows.
+    async fn reconnect(&mut self) -> Result<(), RconError> {
+        let policy = self.reconnect.as_ref().expect("only called with a reconnect policy");
+
+        let mut attempt = 0;
+        loop {
+            tokio::time::sleep(policy.pause(attempt)).await;
+
+            let error = match connect(self.addr, policy.connect_timeout).await {
+                Ok(mut stream) => {
+                    self.session.reset();
+                    match handshake(&mut stream, &mut self.session, &self.password, self.timeouts, None).await {
+                        Ok(()) => {
+                            self.open_stream = stream;
+                            self.broken = false;
+                            self.reconnects += 1;
+                            return Ok(());
+                        },
+                        // trying again does not fix the password.
+                        Err(RconError::AuthFailed) => return Err(RconError::AuthFailed),
+                        Err(e) => e,
+                    }
+                },
+                Err(e) => e,
+            };
+
+            attempt += 1;
+            if attempt >= policy.max_attempts {
+                return Err(RconError::Disconnected(Box::new(error)));
+            }
+        }
+    }
+
+    /// See [`RconClient::set_reconnect_policy`](../client/struct.RconClient.html#method.set_reconnect_policy).
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect = Some(policy);
+    }
+
+    /// See [`RconClient::reconnects`](../client/struct.RconClient.html#method.reconnects).
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+
+    /// See [`RconClient::is_broken`](../client/struct.RconClient.html#method.is_broken).
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// See [`RconClient::stale_packets`](../client/struct.RconClient.html#method.stale_packets).
+    pub fn stale_packets(&self) -> u64 {
+        self.session.stale_packets()
+    }
+
+    /// See [`RconClient::set_read_timeout`](../client/struct.RconClient.html#method.set_read_timeout).
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), RconError> {
+        check_timeout(timeout)?;
+        self.timeouts.read = timeout;
+        Ok(())
+    }
+
+    /// See [`RconClient::set_write_timeout`](../client/struct.RconClient.html#method.set_write_timeout).
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> Result<(), RconError> {
+        check_timeout(timeout)?;
+        self.timeouts.write = timeout;
+        Ok(())
+    }
+
+    /// Probes which termination strategy the server supports and uses it for every following
+    /// command. See [`RconClient::probe`](../client/struct.RconClient.html#method.probe).
+    pub async fn probe(&mut self, probe: &Probe) -> Result<ServerProfile, RconError> {
+        self.session.probe(probe)?;
+
+        loop {
+            if let SessionEvent::Probed(profile) = next_event(&mut self.open_stream, &mut self.session,
+                                                             self.timeouts, None).await? {
+                return Ok(profile);
+            }
+        }
+    }
+
+    /// See [`RconClient::set_text_decoding`](../client/struct.RconClient.html#method.set_text_decoding).
+    pub fn set_text_decoding(&mut self, text_decoding: TextDecoding) {
+        self.text_decoding = text_decoding;
+    }
+
+    /// See [`RconClient::set_max_response_size`](../client/struct.RconClient.html#method.set_max_response_size).
+    pub fn set_max_response_size(&mut self, max_response_size: Option<usize>) {
+        self.session.set_max_response_size(max_response_size);
+    }
+
+    /// Opens up a connection to an RCON server and authenticates with `pass`. The
+    /// parameters are the same as for [`RconClient::open`](../client/struct.RconClient.html#method.open);
+    /// `timeout` bounds both the connect and the wait for the answer to the authentication.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{async_client::*, error::*};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// // connections are established, but the server never answers.
+    /// let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let addr = silent.local_addr().unwrap();
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let opened = AsyncRconClient::open(addr.to_string(), "mypass", None::<String>,
+    ///                                    Some(Duration::from_millis(100))).await;
+    /// assert!(matches!(opened, Err(RconError::Timeout)));
+    /// # });
+    /// ```
+    pub async fn open<A: Into<String>,
+                      P: Into<String>,
+                      C: Into<String>>(addr: A,
+                                       pass: P,
+                                       safe_command: Option<C>,
+                                       timeout: Option<Duration>) -> Result<AsyncRconClient, RconError> {
+        AsyncRconClient::open_with_strategy(addr, pass, default_strategy(safe_command)?, timeout).await
+    }
+
+    /// Opens up a connection which finds the end of responses with `strategy`. See
+    /// [`RconClient::open_with_strategy`](../client/struct.RconClient.html#method.open_with_strategy).
+    pub async fn open_with_strategy<A: Into<String>,
+                                    P: Into<String>,
+                                    T: TerminationStrategy + 'static>(addr: A,
+                                                                      pass: P,
+                                                                      strategy: T,
+                                                                      timeout: Option<Duration>) -> Result<AsyncRconClient, RconError> {
+        AsyncRconClient::open_session(addr, pass, ClientSession::with_strategy(strategy), timeout).await
+    }
+
+    /// Opens up a connection to a server speaking `dialect`. See
+    /// [`RconClient::open_dialect`](../client/struct.RconClient.html#method.open_dialect).
+    pub async fn open_dialect<A: Into<String>,
+                              P: Into<String>>(addr: A,
+                                               pass: P,
+                                               dialect: Dialect,
+                                               timeout: Option<Duration>) -> Result<AsyncRconClient, RconError> {
+        AsyncRconClient::open_session(addr, pass, ClientSession::with_dialect(dialect), timeout).await
+    }
+
+    /// Opens up a connection to a server probed before. See
+    /// [`RconClient::open_with_profile`](../client/struct.RconClient.html#method.open_with_profile).
+    pub async fn open_with_profile<A: Into<String>,
+                                   P: Into<String>>(addr: A,
+                                                    pass: P,
+                                                    profile: &ServerProfile,
+                                                    timeout: Option<Duration>) -> Result<AsyncRconClient, RconError> {
+        AsyncRconClient::open_session(addr, pass, ClientSession::with_profile(profile)?, timeout).await
+    }
+
+    async fn open_session<A: Into<String>,
+                          P: Into<String>>(addr: A,
+                                           pass: P,
+                                           mut session: ClientSession,
+                                           timeout: Option<Duration>) -> Result<AsyncRconClient, RconError> {
+        // building address:
+        let s_addr: String = addr.into();
+        let sock_addr: SocketAddr =
+            s_addr.parse()?;
+        let password: String = pass.into();
+
+        //connect:
+        let mut stream = connect(sock_addr, timeout).await?;
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        handshake(&mut stream, &mut session, &password, Timeouts::default(), deadline).await?;
+        Ok( AsyncRconClient { open_stream: stream,
+                              addr: sock_addr,
+                              session,
+                              password,
+                              text_decoding: TextDecoding::default(),
+                              timeouts: Timeouts::default(),
+                              broken: false,
+                              reconnect: None,
+                              reconnects: 0 })
+    }
+}
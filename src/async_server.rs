@@ -0,0 +1,254 @@
+//! # Async Server
+//!
+//! The async counterpart to the [`server`](../server/index.html) module, built on
+//! [`tokio`](https://docs.rs/tokio). Every connection is served by its own task. It is only
+//! available with the `tokio` feature enabled, and has to run within a tokio runtime.
+//!
+//! The protocol handling is exactly the one of [`RconServer`](../server/struct.RconServer.html):
+//! the same auth replies, the same splitting of large responses, the same
+//! [`Limits`](../limits/struct.Limits.html). Handlers and authenticators are the blocking
+//! [`CommandHandler`](../server/trait.CommandHandler.html) and
+//! [`Authenticator`](../auth/trait.Authenticator.html); they are called on tokio's blocking
+//! threads, so a slow command or password hash does not stall other connections.
+//!
+//! ## Shutdown
+//!
+//! [`serve_with_shutdown`](struct.AsyncRconServer.html#method.serve_with_shutdown) takes a
+//! future which signals the shutdown, e.g. `tokio::signal::ctrl_c()`. Once it completes, the
+//! server stops accepting connections. Commands already being handled are finished and
+//! their responses sent, then every connection is shut down. The method returns after all
+//! connections are closed.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{async_client::*, async_server::*, server::CommandRequest};
+//! use tokio::sync::oneshot;
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let server =
+//!     AsyncRconServer::bind("127.0.0.1:0", "mypass",
+//!                           |request: &CommandRequest| format!("echo: {}", request.command))
+//!     .await
+//!     .unwrap();
+//! let addr = server.local_addr().unwrap();
+//!
+//! let (stop, stopped) = oneshot::channel::<()>();
+//! let serving = tokio::spawn(server.serve_with_shutdown(async { let _ = stopped.await; }));
+//!
+//! let mut rcon =
+//!     AsyncRconClient::open(addr.to_string(), "mypass", None::<String>, None).await.unwrap();
+//! assert_eq!(rcon.exec("/help").await.unwrap(), "echo: /help");
+//!
+//! stop.send(()).unwrap();
+//! serving.await.unwrap();
+//!
+//! // the connection was closed by the shutdown.
+//! assert!(rcon.exec("/help").await.is_err());
+//! # });
+//! ```
+
+use super::auth::*;
+use super::limits::*;
+use super::raw_packet::*;
+use super::server::*;
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// An async RCON server on tokio. See the [module documentation](index.html).
+pub struct AsyncRconServer<H> {
+    listener: TcpListener,
+    authenticator: Arc<dyn Authenticator>,
+    handler: Arc<H>,
+    limits: Limits,
+    observer: Arc<dyn ServerObserver>,
+}
+
+impl<H: CommandHandler + 'static> AsyncRconServer<H> {
+    /// Binds the server to `addr`. See [`RconServer::bind`](../server/struct.RconServer.html#method.bind).
+    pub async fn bind<A: ToSocketAddrs,
+                      Au: Authenticator + 'static>(addr: A,
+                                                   authenticator: Au,
+                                                   handler: H) -> io::Result<AsyncRconServer<H>> {
+        let listener = TcpListener::bind(addr).await?;
+
+        Ok(
+            AsyncRconServer {
+                listener,
+                authenticator: Arc::new(authenticator),
+                handler: Arc::new(handler),
+                limits: Limits::default(),
+                observer: Arc::new(()),
+            })
+    }
+
+    /// Sets the limits of the server; without, there are none.
+    pub fn with_limits(mut self, limits: Limits) -> AsyncRconServer<H> {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the observer told about connections, authentications, bans and timeouts.
+    pub fn with_observer<O: ServerObserver + 'static>(mut self, observer: O) -> AsyncRconServer<H> {
+        self.observer = Arc::new(observer);
+        self
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections and serves each of them in its own task. Never returns; errors
+    /// accepting a connection are handled as by [`RconServer::serve`](../server/struct.RconServer.html#method.serve).
+    pub async fn serve(self) {
+        self.serve_with_shutdown(std::future::pending()).await
+    }
+
+    /// Like [`serve`](#method.serve), but shuts the server down gracefully as soon as
+    /// `shutdown` completes. See the [module documentation](index.html#shutdown).
+    ///
+    /// Errors accepting a connection are reported to the observer's
+    /// [`accept_failed`](../limits/trait.ServerObserver.html#method.accept_failed) and do
+    /// not stop the server; only `shutdown` does.
+    pub async fn serve_with_shutdown<F: Future<Output = ()>>(self, shutdown: F) {
+        let gate = Arc::new(Gatekeeper::new(self.limits, self.observer));
+        let (stop, stopped) = watch::channel(false);
+        let mut connections = JoinSet::new();
+
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown =>
+                    break,
+                accepted = self.listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            gate.observer.accept_failed(&e);
+                            if !is_connection_error(&e) {
+                                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                            }
+                            continue;
+                        },
+                    };
+                    // rejected connections are closed by dropping the stream.
+                    let permit = match gate.admit(peer) {
+                        Some(permit) => permit,
+                        None => continue,
+                    };
+
+                    let authenticator = Arc::clone(&self.authenticator);
+                    let handler = Arc::clone(&self.handler);
+                    let stopped = stopped.clone();
+                    connections.spawn(async move {
+                        // a failing connection only ends this connection.
+                        let _ = serve_connection(stream, peer, authenticator, handler, permit, stopped).await;
+                    });
+                },
+                // reaps finished connections, so they do not pile up.
+                Some(_) = connections.join_next(), if !connections.is_empty() => {},
+            }
+        }
+
+        drop(self.listener);
+        let _ = stop.send(true);
+        while connections.join_next().await.is_some() {}
+    }
+}
+
+async fn serve_connection<H>(mut stream: TcpStream,
+                             peer: SocketAddr,
+                             authenticator: Arc<dyn Authenticator>,
+                             handler: Arc<H>,
+                             permit: Permit,
+                             mut stopped: watch::Receiver<bool>) -> io::Result<()>
+    where H: CommandHandler + 'static {
+    let gate = Arc::clone(permit.gate());
+    let mut connection = ServerConnection::new(peer);
+
+    loop {
+        let deadline = connection.deadline(&gate.limits);
+
+        // only waiting for a packet is interrupted by the shutdown, never a command.
+        let packet = tokio::select! {
+            _ = stopped.wait_for(|&stop| stop) =>
+                break,
+            packet = read_packet(&mut stream, deadline) =>
+                match packet {
+                    Ok(packet) => packet,
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                        if let Some((_, kind)) = deadline {
+                            gate.observer.timed_out(peer, kind);
+                        }
+                        return Err(e);
+                    },
+                    Err(e) =>
+                        return Err(e),
+                },
+        };
+
+        let authenticator = Arc::clone(&authenticator);
+        let handler = Arc::clone(&handler);
+        let handler_gate = Arc::clone(&gate);
+        let (returned, reply) =
+            tokio::task::spawn_blocking(move || {
+                let reply = connection.handle(&packet, &*authenticator, &*handler, &handler_gate);
+                (connection, reply)
+            })
+            .await
+            .map_err(io::Error::other)?;
+        connection = returned;
+
+        match reply {
+            Reply::Send(packets) =>
+                write_packets(&mut stream, &packets).await?,
+            Reply::SendAndClose(packets) => {
+                write_packets(&mut stream, &packets).await?;
+                break;
+            },
+            Reply::Close =>
+                break,
+        }
+    }
+
+    stream.shutdown().await
+}
+
+async fn read_packet(stream: &mut TcpStream,
+                     deadline: Option<(std::time::Instant, TimeoutKind)>) -> io::Result<RawPacket> {
+    let read = async {
+        let psize = stream.read_i32_le().await?;
+
+        // the frame is the packet size plus the size field itself.
+        let mut frame = psize.to_le_bytes().to_vec();
+        frame.resize(4 + frame_size(psize)?, 0);
+        stream.read_exact(&mut frame[4..]).await?;
+
+        Ok(decode_frame(&frame)?)
+    };
+
+    match deadline {
+        Some((deadline, _)) =>
+            tokio::time::timeout_at(deadline.into(), read).await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Client did not send in time."))?,
+        None =>
+            read.await,
+    }
+}
+
+async fn write_packets(stream: &mut TcpStream, packets: &[RawPacket]) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    for packet in packets {
+        packet.serialize_into(&mut buffer);
+    }
+    stream.write_all(&buffer).await
+}
@@ -0,0 +1,291 @@
+//! # Authentication
+//!
+//! The protocol knows a single password per server. To tell users apart, an
+//! [`RconServer`](../server/struct.RconServer.html) checks passwords with an
+//! [`Authenticator`](trait.Authenticator.html), which returns the [`Identity`](struct.Identity.html)
+//! of the user. The identity is passed on to the command handler with every command, so
+//! actions can be attributed and permissions checked.
+//!
+//! Authenticators for several users expect the password in the form `name:password`, since
+//! the protocol has no field for a user name.
+//!
+//! - [`StaticPassword`](struct.StaticPassword.html): the classic single password. A plain
+//!   `&str` or `String` works as well.
+//! - [`Users`](struct.Users.html): several named users with plain text passwords.
+//! - [`PasswordFile`](struct.PasswordFile.html): several named users with argon2 or bcrypt
+//!   hashed passwords. Only available with the `hashed-passwords` feature enabled.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::auth::*;
+//!
+//! let users = Users::new()
+//!     .user("alice", "secret", &["admin"])
+//!     .user("bob", "hunter2", &[]);
+//!
+//! let alice = users.authenticate(b"alice:secret").unwrap();
+//! assert_eq!(alice.name(), "alice");
+//! assert!(alice.has_role("admin"));
+//!
+//! assert!(users.authenticate(b"bob:secret").is_none());
+//! assert!(users.authenticate(b"secret").is_none());
+//! ```
+
+/// The user a connection authenticated as.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Identity {
+    name: String,
+    roles: Vec<String>,
+}
+
+impl Identity {
+    /// Creates the identity of the user `name` having the roles `roles`.
+    pub fn new<N: Into<String>>(name: N, roles: &[&str]) -> Identity {
+        Identity {
+            name: name.into(),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+        }
+    }
+
+    /// The name of the user.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The roles of the user.
+    pub fn roles(&self) -> &[String] {
+        &self.roles
+    }
+
+    /// Returns `true` if the user has the role `role`.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+/// Checks the password of an auth request.
+pub trait Authenticator: Send + Sync {
+    /// Returns the identity belonging to `password`, or `None` if the password is wrong.
+    fn authenticate(&self, password: &[u8]) -> Option<Identity>;
+}
+
+/// Compared against for unknown user names, so the time taken does not tell which names
+/// exist.
+const DUMMY_PASSWORD: &[u8] = b"unknown user";
+
+/// Compares without returning early, so the time taken does not tell how much of
+/// the password was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Splits `name:password`.
+fn split_credentials(credentials: &[u8]) -> Option<(&str, &[u8])> {
+    let colon = credentials.iter().position(|&b| b == b':')?;
+    let name = std::str::from_utf8(&credentials[..colon]).ok()?;
+    Some((name, &credentials[colon + 1..]))
+}
+
+/// A single password for everyone, as the protocol intends it. Authenticated connections
+/// get the identity given to [`with_identity`](#method.with_identity), by default a user
+/// named `rcon` without any roles.
+#[derive(Debug, Clone)]
+pub struct StaticPassword {
+    password: String,
+    identity: Identity,
+}
+
+impl StaticPassword {
+    /// Accepts `password`.
+    pub fn new<P: Into<String>>(password: P) -> StaticPassword {
+        StaticPassword { password: password.into(), identity: Identity::new("rcon", &[]) }
+    }
+
+    /// Sets the identity of authenticated connections.
+    pub fn with_identity(mut self, identity: Identity) -> StaticPassword {
+        self.identity = identity;
+        self
+    }
+}
+
+impl Authenticator for StaticPassword {
+    fn authenticate(&self, password: &[u8]) -> Option<Identity> {
+        if constant_time_eq(password, self.password.as_bytes()) {
+            Some(self.identity.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl Authenticator for String {
+    fn authenticate(&self, password: &[u8]) -> Option<Identity> {
+        StaticPassword::new(self.as_str()).authenticate(password)
+    }
+}
+
+impl Authenticator for &'static str {
+    fn authenticate(&self, password: &[u8]) -> Option<Identity> {
+        StaticPassword::new(*self).authenticate(password)
+    }
+}
+
+/// Several named users with plain text passwords, authenticating with `name:password`.
+/// See the [module documentation](index.html).
+#[derive(Debug, Clone, Default)]
+pub struct Users {
+    users: Vec<(Identity, String)>,
+}
+
+impl Users {
+    /// Creates an authenticator without any users.
+    pub fn new() -> Users {
+        Users::default()
+    }
+
+    /// Adds the user `name` with the password `password` and the roles `roles`.
+    pub fn user<N: Into<String>, P: Into<String>>(mut self, name: N, password: P, roles: &[&str]) -> Users {
+        self.users.push((Identity::new(name, roles), password.into()));
+        self
+    }
+}
+
+impl Authenticator for Users {
+    fn authenticate(&self, password: &[u8]) -> Option<Identity> {
+        let (name, password) = split_credentials(password)?;
+
+        let user = self.users.iter().find(|(identity, _)| identity.name() == name);
+        let expected = user.map_or(DUMMY_PASSWORD, |(_, expected)| expected.as_bytes());
+        let matches = constant_time_eq(password, expected);
+
+        user.filter(|_| matches).map(|(identity, _)| identity.clone())
+    }
+}
+
+#[cfg(feature = "hashed-passwords")]
+pub use self::password_file::*;
+
+#[cfg(feature = "hashed-passwords")]
+mod password_file {
+    use super::*;
+
+    use std::convert::TryFrom;
+    use std::io;
+    use std::path::Path;
+
+    use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+    use argon2::password_hash::SaltString;
+
+    /// Several named users with hashed passwords, authenticating with `name:password`.
+    ///
+    /// The users are read from lines of the form `name:hash` or `name:hash:role,role`, where
+    /// `hash` is either an argon2 hash in PHC format (`$argon2id$...`) or a bcrypt hash
+    /// (`$2b$...`). Empty lines and lines starting with `#` are skipped.
+    ///
+    /// Unknown user names are checked against a dummy hash with the scheme and cost of the
+    /// first user's hash, so a file should use the same scheme and cost for all users.
+    ///
+    /// Only available with the `hashed-passwords` feature enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::auth::*;
+    ///
+    /// let hash = bcrypt::hash("secret", 4).unwrap();
+    /// let users = PasswordFile::parse(&format!("# ops team\nalice:{}:admin\n", hash)).unwrap();
+    ///
+    /// assert_eq!(users.authenticate(b"alice:secret"), Some(Identity::new("alice", &["admin"])));
+    /// assert_eq!(users.authenticate(b"alice:wrong"), None);
+    /// assert_eq!(users.authenticate(b"mallory:secret"), None);
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct PasswordFile {
+        users: Vec<(Identity, String)>,
+        dummy_hash: Option<String>,
+    }
+
+    impl PasswordFile {
+        /// Reads the users from the file at `path`.
+        pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PasswordFile> {
+            PasswordFile::parse(&std::fs::read_to_string(path)?)
+        }
+
+        /// Reads the users from `content`. Fails with `InvalidData` on lines without hash
+        /// or with a hash of an unknown format.
+        pub fn parse(content: &str) -> io::Result<PasswordFile> {
+            let mut users = Vec::new();
+
+            for (number, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                let invalid = |reason: &str| {
+                    io::Error::new(io::ErrorKind::InvalidData,
+                                   format!("line {}: {}", number + 1, reason))
+                };
+
+                let mut fields = line.splitn(3, ':');
+                let name = fields.next().unwrap_or_default();
+                let hash = fields.next().ok_or_else(|| invalid("missing password hash"))?;
+                let roles: Vec<&str> =
+                    fields.next()
+                    .map(|roles| roles.split(',').filter(|r| !r.is_empty()).collect())
+                    .unwrap_or_default();
+
+                if !(hash.starts_with("$argon2") || hash.starts_with("$2")) {
+                    return Err(invalid("unknown password hash format"));
+                }
+                users.push((Identity::new(name, &roles), hash.to_string()));
+            }
+
+            let dummy_hash = users.first().and_then(|(_, hash)| dummy_hash(hash));
+            Ok(PasswordFile { users, dummy_hash })
+        }
+    }
+
+    // A hash of `DUMMY_PASSWORD` with the scheme and cost of `hash`, verified against for
+    // unknown user names, so a miss takes as long as a hit.
+    fn dummy_hash(hash: &str) -> Option<String> {
+        if hash.starts_with("$argon2") {
+            let hash = PasswordHash::new(hash).ok()?;
+            let algorithm = Algorithm::try_from(hash.algorithm).ok()?;
+            let version = hash.version.map_or(Ok(Version::default()), Version::try_from).ok()?;
+            let params = Params::try_from(&hash).ok()?;
+            let salt = SaltString::encode_b64(b"facio dummy salt").ok()?;
+            Argon2::new(algorithm, version, params)
+                .hash_password(DUMMY_PASSWORD, &salt)
+                .map(|hash| hash.to_string())
+                .ok()
+        } else {
+            let cost = hash.split('$').nth(2)?.parse().ok()?;
+            bcrypt::hash(DUMMY_PASSWORD, cost).ok()
+        }
+    }
+
+    fn verify(password: &[u8], hash: &str) -> bool {
+        if hash.starts_with("$argon2") {
+            PasswordHash::new(hash)
+                .map(|hash| Argon2::default().verify_password(password, &hash).is_ok())
+                .unwrap_or(false)
+        } else {
+            bcrypt::verify(password, hash).unwrap_or(false)
+        }
+    }
+
+    impl Authenticator for PasswordFile {
+        fn authenticate(&self, password: &[u8]) -> Option<Identity> {
+            let (name, password) = split_credentials(password)?;
+
+            let user = self.users.iter().find(|(identity, _)| identity.name() == name);
+            let hash = user.map(|(_, hash)| hash).or(self.dummy_hash.as_ref());
+            let matches = hash.is_some_and(|hash| verify(password, hash));
+
+            user.filter(|_| matches).map(|(identity, _)| identity.clone())
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! # Chaos
+//!
+//! A fault-injecting transport for testing clients under adversarial network conditions.
+//! [`ChaosStream`](struct.ChaosStream.html) wraps any blocking stream, e.g. the `TcpStream`
+//! to a [`MockServer`](../mock/struct.MockServer.html), and disturbs the traffic according to
+//! a [`Chaos`](struct.Chaos.html) schedule:
+//!
+//! - latency before reads and writes,
+//! - writes fragmented into single bytes,
+//! - disconnects in the middle of a packet,
+//! - received packets with a corrupted `psize` field,
+//! - received packets delivered twice.
+//!
+//! The schedule is random, but seeded: the same seed injects the same faults into the same
+//! traffic, so a failing test can be reproduced. Every injected fault is recorded, see
+//! [`faults`](struct.ChaosStream.html#method.faults).
+//!
+//! The stream works with [`ll`](../ll/index.html) and with
+//! [`RconClient::from_stream`](../client/struct.RconClient.html#method.from_stream). Only
+//! available with the `test-util` feature enabled.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{chaos::*, client::*, error::*, mock::*};
+//! use std::net::TcpStream;
+//! use std::time::Duration;
+//!
+//! let mock = MockServer::new("mypass").respond("status", "players: 3").start().unwrap();
+//!
+//! // a slow and fragmenting network does not bother the client.
+//! let chaos = Chaos::new(42)
+//!     .latency(0.5, Duration::from_millis(5))
+//!     .fragment_writes(1.0);
+//! let stream = ChaosStream::new(TcpStream::connect(mock.addr()).unwrap(), chaos);
+//! let mut rcon = RconClient::from_stream(stream, "mypass", Some("echo"), None).unwrap();
+//! assert_eq!(rcon.exec("status").unwrap(), "players: 3");
+//! assert!(rcon.get_ref().faults().contains(&Fault::FragmentedWrite));
+//!
+//! // a corrupted packet size is a protocol violation.
+//! let chaos = Chaos::new(42).corrupt_psize(1.0);
+//! let stream = ChaosStream::new(TcpStream::connect(mock.addr()).unwrap(), chaos);
+//! assert!(matches!(RconClient::from_stream(stream, "mypass", Some("echo"), None),
+//!                  Err(RconError::Protocol(ProtocolError::Decode(_)))));
+//! ```
+
+use super::client::ReadTimeout;
+use super::raw_packet::*;
+
+use std::io;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::Duration;
+
+/// A fault a [`ChaosStream`](struct.ChaosStream.html) injected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Fault {
+    /// A read or write was delayed.
+    Latency(Duration),
+    /// A write was split into single bytes, each written and flushed on its own.
+    FragmentedWrite,
+    /// The connection was cut in the middle of a packet. Reads end there, writes fail.
+    Disconnect,
+    /// The `psize` of a received packet was replaced by an invalid one.
+    CorruptedPsize(i32),
+    /// A received packet was delivered twice.
+    DuplicatedPacket,
+}
+
+/// The schedule of a [`ChaosStream`](struct.ChaosStream.html): how likely every kind of
+/// fault is, and the seed of the random choices. Every probability is between `0.0`
+/// (never, the default) and `1.0` (always).
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    seed: u64,
+    latency: (f64, Duration),
+    fragment_writes: f64,
+    disconnect: f64,
+    corrupt_psize: f64,
+    duplicate: f64,
+    max_packet_size: usize,
+}
+
+impl Chaos {
+    /// Creates a schedule without any faults, seeded with `seed`.
+    pub fn new(seed: u64) -> Chaos {
+        Chaos {
+            seed,
+            latency: (0.0, Duration::ZERO),
+            fragment_writes: 0.0,
+            disconnect: 0.0,
+            corrupt_psize: 0.0,
+            duplicate: 0.0,
+            max_packet_size: MAX_PACKET_SIZE as usize,
+        }
+    }
+
+    /// Delays reads of a packet and writes with `probability`, by up to `max`.
+    pub fn latency(mut self, probability: f64, max: Duration) -> Chaos {
+        self.latency = (probability, max);
+        self
+    }
+
+    /// Splits writes into single bytes with `probability`.
+    pub fn fragment_writes(mut self, probability: f64) -> Chaos {
+        self.fragment_writes = probability;
+        self
+    }
+
+    /// Cuts the connection in the middle of a received packet or a write with `probability`.
+    pub fn disconnect(mut self, probability: f64) -> Chaos {
+        self.disconnect = probability;
+        self
+    }
+
+    /// Corrupts the `psize` of received packets with `probability`.
+    pub fn corrupt_psize(mut self, probability: f64) -> Chaos {
+        self.corrupt_psize = probability;
+        self
+    }
+
+    /// Delivers received packets twice with `probability`.
+    pub fn duplicate_packets(mut self, probability: f64) -> Chaos {
+        self.duplicate = probability;
+        self
+    }
+
+    /// Accepts received packets up to a `psize` of `max` as packets, e.g. the
+    /// [`max_packet_size`](../dialect/enum.Dialect.html#method.max_packet_size) of a dialect
+    /// whose server does not split responses. The default is `MAX_PACKET_SIZE`.
+    pub fn max_packet_size(mut self, max: usize) -> Chaos {
+        self.max_packet_size = max;
+        self
+    }
+}
+
+/// SplitMix64; small, fast and good enough to schedule faults.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        // 53 random bits give a uniform float in [0, 1).
+        probability > 0.0 && ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    /// A number in `0..bound`; `bound` must not be 0.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+/// A stream injecting faults into the traffic of the wrapped stream `S`. See the
+/// [module documentation](index.html).
+///
+/// Received data is handled packet by packet, so faults hit whole packets; writes are
+/// disturbed per call of `write`. A packet whose reading was interrupted, e.g. by a read
+/// timeout, is completed by the next read.
+///
+/// # Example
+///
+/// ```
+/// use facio::{chaos::*, dialect::*, raw_packet::*};
+/// use std::io::{self, Read};
+///
+/// // a stream which times out in the middle of a packet larger than `MAX_PACKET_SIZE`.
+/// struct Stuttering(Vec<io::Result<Vec<u8>>>);
+///
+/// impl Read for Stuttering {
+///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+///         if self.0.is_empty() {
+///             return Ok(0);
+///         }
+///         let bytes = self.0.remove(0)?;
+///         buf[..bytes.len()].copy_from_slice(&bytes);
+///         Ok(bytes.len())
+///     }
+/// }
+///
+/// let mut packet = Vec::new();
+/// RawPacket::new_response_value(1, "").unwrap().serialize(&mut packet).unwrap();
+/// packet[..4].copy_from_slice(&(10i32 + 3000).to_le_bytes());
+/// packet.splice(12..12, vec![b'x'; 3000]);
+///
+/// let inner = Stuttering(vec![Ok(packet[..6].to_vec()),
+///                             Err(io::ErrorKind::WouldBlock.into()),
+///                             Ok(packet[6..3006].to_vec()),
+///                             Ok(packet[3006..].to_vec())]);
+/// let chaos = Chaos::new(1)
+///     .duplicate_packets(1.0)
+///     .max_packet_size(Dialect::Factorio.max_packet_size());
+/// let mut stream = ChaosStream::new(inner, chaos);
+///
+/// let mut buf = [0u8; 4096];
+/// assert_eq!(stream.read(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+/// let mut received = Vec::new();
+/// loop {
+///     match stream.read(&mut buf).unwrap() {
+///         0 => break,
+///         read => received.extend_from_slice(&buf[..read]),
+///     }
+/// }
+/// // the whole packet, delivered twice.
+/// assert_eq!(received, [packet.clone(), packet].concat());
+/// ```
+pub struct ChaosStream<S> {
+    inner: S,
+    chaos: Chaos,
+    rng: Rng,
+    /// Received bytes which do not form a whole packet yet.
+    incoming: Vec<u8>,
+    pending: Vec<u8>,
+    position: usize,
+    disconnected: bool,
+    faults: Vec<Fault>,
+}
+
+impl<S> ChaosStream<S> {
+    /// Wraps `inner`, disturbing it according to `chaos`.
+    pub fn new(inner: S, chaos: Chaos) -> ChaosStream<S> {
+        ChaosStream {
+            inner,
+            rng: Rng(chaos.seed),
+            chaos,
+            incoming: Vec::new(),
+            pending: Vec::new(),
+            position: 0,
+            disconnected: false,
+            faults: Vec::new(),
+        }
+    }
+
+    /// All faults injected so far, in order.
+    pub fn faults(&self) -> &[Fault] {
+        &self.faults
+    }
+
+    /// The wrapped stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwraps the stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn delay(&mut self) {
+        let (probability, max) = self.chaos.latency;
+        if self.rng.chance(probability) {
+            let delay = Duration::from_micros(self.rng.below(max.as_micros() as u64 + 1));
+            self.faults.push(Fault::Latency(delay));
+            thread::sleep(delay);
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.disconnected = true;
+        self.faults.push(Fault::Disconnect);
+    }
+}
+
+impl<S: Read> ChaosStream<S> {
+    /// The length of the frame at the start of `incoming`, as far as it is known yet.
+    fn frame_length(&self) -> usize {
+        if self.incoming.len() < 4 {
+            return 4;
+        }
+        let psize = i32::from_le_bytes([self.incoming[0], self.incoming[1], self.incoming[2], self.incoming[3]]);
+        // a packet of invalid size is passed on as it is; the reader has to deal with it.
+        frame_size_within(psize, self.chaos.max_packet_size).map_or(4, |size| 4 + size)
+    }
+
+    /// Reads the next packet of the wrapped stream into `pending`, disturbed. Bytes of a
+    /// packet read before a read fails, e.g. since it timed out, are kept for the next call.
+    fn next_packet(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 4096];
+        while self.incoming.len() < self.frame_length() {
+            let read = self.inner.read(&mut buffer)?;
+            if read == 0 {
+                // the connection ended; whatever is left is passed on as it is.
+                break;
+            }
+            self.incoming.extend_from_slice(&buffer[..read]);
+        }
+        let length = self.frame_length().min(self.incoming.len());
+        let mut frame: Vec<u8> = self.incoming.drain(..length).collect();
+        if frame.is_empty() {
+            self.pending = frame;
+            self.position = 0;
+            return Ok(());
+        }
+        self.delay();
+
+        if frame.len() >= 4 && self.rng.chance(self.chaos.corrupt_psize) {
+            let max = self.chaos.max_packet_size.min(i32::MAX as usize - 1) as i32;
+            let psize = if self.rng.chance(0.5) {
+                -(self.rng.below(i32::MAX as u64) as i32) - 1
+            } else {
+                max + 1 + self.rng.below((i32::MAX - max) as u64) as i32
+            };
+            frame[..4].copy_from_slice(&psize.to_le_bytes());
+            self.faults.push(Fault::CorruptedPsize(psize));
+        }
+        if self.rng.chance(self.chaos.duplicate) {
+            frame.extend_from_within(..);
+            self.faults.push(Fault::DuplicatedPacket);
+        }
+        if frame.len() > 1 && self.rng.chance(self.chaos.disconnect) {
+            let cut = 1 + self.rng.below(frame.len() as u64 - 1) as usize;
+            frame.truncate(cut);
+            self.disconnect();
+        }
+
+        self.pending = frame;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<S: Read> Read for ChaosStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.pending.len() {
+            if self.disconnected {
+                return Ok(0);
+            }
+            self.next_packet()?;
+        }
+
+        let available = &self.pending[self.position..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+impl<S: ReadTimeout> ReadTimeout for ChaosStream<S> {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_write_timeout(timeout)
+    }
+}
+
+impl<S: Write> Write for ChaosStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.disconnected {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "Disconnected by chaos."));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.delay();
+
+        if self.rng.chance(self.chaos.disconnect) {
+            let cut = self.rng.below(buf.len() as u64) as usize;
+            self.inner.write_all(&buf[..cut])?;
+            self.inner.flush()?;
+            self.disconnect();
+            return Err(io::Error::new(io::ErrorKind::ConnectionReset, "Disconnected by chaos."));
+        }
+        if self.rng.chance(self.chaos.fragment_writes) {
+            self.faults.push(Fault::FragmentedWrite);
+            for byte in buf {
+                self.inner.write_all(std::slice::from_ref(byte))?;
+                self.inner.flush()?;
+            }
+            return Ok(buf.len());
+        }
+
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -4,16 +4,15 @@
 //! [`TcpStream`](https://doc.rust-lang.org/std/net/struct.TcpStream.html) from the
 //! standard library, this part of the library implements a RCON client.
 //!
-//! As of now (which means: before `async/await` is stable), this client is
-//! synchronous only. It is a future project to extend this to an async client,
-//! whenever the feature hits stable.
+//! This client is blocking. With the `tokio` feature enabled, the
+//! [`async_client`](../async_client/index.html) module offers the same API as an async client.
 //!
 //! ## Example
 //!
-//! ```
-//! use facio::{raw_packet::*, client::*};
+//! ```no_run
+//! use facio::{raw_packet::*, client::*, error::*};
 //!
-//! fn main() -> std::io::Result<()> {
+//! fn main() -> Result<(), RconError> {
 //!    // open the rcon connection where `mypass` is the password and
 //!    // echoing `echo` is used as the safe/check command (see below).
 //!    // The last `None` denotes that the connection attempt has no timeout.
@@ -29,10 +28,27 @@
 //!    } else {
 //!        println!("Error?");
 //!    }
-//! 
+//!
+//!    Ok(())
 //! } // connection is closed here.
 //! ```
 //!
+//! ## Errors
+//!
+//! All functions report errors as [`RconError`](../error/enum.RconError.html), which
+//! distinguishes e.g. a wrong password (`RconError::AuthFailed`) from a failing
+//! connection (`RconError::Io`):
+//!
+//! ```no_run
+//! use facio::{client::*, error::*};
+//!
+//! match RconClient::open("127.0.0.1:38742", "mypass", Some("echo"), None) {
+//!     Ok(_rcon) => println!("connected"),
+//!     Err(RconError::AuthFailed) => println!("wrong password"),
+//!     Err(e) => println!("cannot connect: {}", e),
+//! }
+//! ```
+//!
 //! ## Safe/Check Command
 //!
 //! Since the protocol allows multi-packet response but does not provide any solution to
@@ -75,65 +91,144 @@
 //! packet ids; they are not longer visible from the outside of the higher-level abstraction
 //! around `exec`.
 //!
+//! Every command gets an id of its own, counting up, and its control packet the negated one.
+//! Packets which answer an earlier command, e.g. one which timed out, are recognized by their
+//! id and skipped instead of being taken for a part of the current response.
+//!
 //! As a lower-level entry point which does not manage multi-packet responses but allows
 //! for an own implementation, there is the [`ll`](../ll/index.html) module.
 
-use super::ll::*;
-use super::raw_packet::*;
+use super::session::*;
+use super::raw_packet::TextDecoding;
+use super::error::RconError;
+use super::termination::TerminationStrategy;
+use super::probe::{Probe, ServerProfile};
+use super::dialect::Dialect;
+use super::reconnect::ReconnectPolicy;
 
 use std::net::{SocketAddr};
 use std::io;
-use std::time::Duration;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
 use std::io::{Error, ErrorKind};
 use std::net::TcpStream;
 
-const CONTROL_ID: i32 = -1; // used as the id for check packets
-const START_ID: i32 = 0; // used as the id for normal packets
+// Writes everything the session queued to the stream. For `exec` this is the command
+// together with the control packet, which hence leave in a single write.
+fn flush_session<S: Write>(stream: &mut S, session: &mut ClientSession) -> io::Result<()> {
+    let outgoing = session.take_outgoing();
+    stream.write_all(&outgoing)
+}
 
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
 
-// The hole next section is kind of a hack. Some RCON Servers implement a double back response
-// for an auth request. They send first a ResponseValue, then a ResponseAuth. Some servers just
-// send a ResponseAuth.
-// The recv_auth functions allows both ways.
-//
-// This might result in a blocking call, if the server just sends a ResponseValue without a follow-up.
-enum AuthCheck {
-    Invalid, NoAuth, Valid
+// Rejects a zero timeout, as `TcpStream` does; it would let every read or write fail.
+pub(crate) fn check_timeout(timeout: Option<Duration>) -> Result<(), RconError> {
+    if timeout == Some(Duration::ZERO) {
+        return Err(Error::new(ErrorKind::InvalidInput, "Timeout must not be zero.").into());
+    }
+    Ok(())
 }
-fn check_auth(packet_id: i32, packet: &RawPacket) -> AuthCheck {
-    if packet.response_type() == Some(PacketType::ResponseAuth) {
-        if packet.pid == packet_id {
-            AuthCheck::Valid
-        } else {
-            AuthCheck::Invalid
-        }
-    } else {
-        AuthCheck::NoAuth
+
+// Whether `e` means the connection is lost, rather than e.g. the server rejecting a command.
+fn is_connection_lost(e: &RconError) -> bool {
+    match e {
+        RconError::Io(e) => !is_timeout(e),
+        RconError::Protocol(_) => true,
+        _ => false,
     }
 }
 
-fn recv_auth(stream: &mut TcpStream, packet_id: i32) -> io::Result<bool> {
-    let response =
-        recv_packet(stream)?;
-
-    match check_auth(packet_id, &response) {
-        AuthCheck::NoAuth => {
-            let response_auth =
-                recv_packet(stream)?;
-            match check_auth(packet_id, &response_auth) {
-                AuthCheck::NoAuth =>
-                    Err(
-                        Error::new(ErrorKind::Other,
-                                   "No valid authentication protocol by server.")),
-                AuthCheck::Invalid =>
-                    Ok(false),
-                AuthCheck::Valid =>
-                    Ok(true),
-            }
-        },
-        AuthCheck::Valid => Ok(true),
-        AuthCheck::Invalid => Ok(false),
-    } 
+// Authenticates `session` with `pass` on `stream`. See `next_event` for the timeouts.
+fn handshake<S: Read + Write + ReadTimeout>(stream: &mut S,
+                                            session: &mut ClientSession,
+                                            pass: &str,
+                                            read_timeout: Option<Duration>,
+                                            deadline: Option<Instant>) -> Result<(), RconError> {
+    // building auth package:
+    session.authenticate(pass)?;
+
+    // sending auth
+    flush_session(stream, session)?;
+    // ... and recv result:
+    match next_event(stream, session, read_timeout, deadline)? {
+        SessionEvent::AuthOk => Ok(()),
+        _ => Err(RconError::AuthFailed),
+    }
+}
+
+/// A blocking stream whose reads can time out, as [`RconClient`](struct.RconClient.html)
+/// needs it for termination strategies based on a quiet period and for its timeouts.
+pub trait ReadTimeout {
+    /// Sets the timeout of reads; `None` means reads block until data arrives. A read which
+    /// timed out fails with `ErrorKind::WouldBlock` or `ErrorKind::TimedOut`.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Sets the timeout of writes, like `set_read_timeout` for reads. Streams which cannot
+    /// time out writes keep the default, which ignores the timeout.
+    fn set_write_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ReadTimeout for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+}
+
+// Reads from the stream and feeds the session until it has an event. Whatever the session
+// queues meanwhile, e.g. the next probe packet, is written before reading.
+//
+// Fails with `RconError::Timeout` if a single read takes longer than `read_timeout`, or the
+// `deadline` passes. Otherwise, this might result in a blocking call, if the server does not
+// send what the session waits for.
+fn next_event<S: Read + Write + ReadTimeout>(stream: &mut S,
+                                             session: &mut ClientSession,
+                                             read_timeout: Option<Duration>,
+                                             deadline: Option<Instant>) -> Result<SessionEvent, RconError> {
+    let mut buffer = [0u8; 4096];
+    loop {
+        if let Some(event) = session.poll_event() {
+            return Ok(event);
+        }
+
+        flush_session(stream, session)?;
+
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining == Some(Duration::ZERO) {
+            return Err(RconError::Timeout);
+        }
+        let timeout = read_timeout.into_iter().chain(remaining).min();
+        let quiet_period = session.quiet_period();
+        let wait = quiet_period.into_iter().chain(timeout).min();
+
+        stream.set_read_timeout(wait)?;
+        let read = match stream.read(&mut buffer) {
+            Ok(read) => read,
+            Err(e) if wait.is_some() && is_timeout(&e) => {
+                // the quiet period wins a tie, its response might be complete.
+                if quiet_period.is_some() && quiet_period == wait {
+                    session.quiet_period_elapsed();
+                    continue;
+                }
+                return Err(RconError::Timeout);
+            },
+            Err(e) => return Err(e.into()),
+        };
+        if read == 0 {
+            return Err(
+                Error::new(ErrorKind::UnexpectedEof, "Connection closed by server.").into());
+        }
+        session.feed(&buffer[..read])?;
+    }
 }
 
 
@@ -143,57 +238,398 @@ fn recv_auth(stream: &mut TcpStream, packet_id: i32) -> io::Result<bool> {
 /// It is certainly *not* safe to share this in concurrent
 /// applications. There should always be only *one* thread at
 /// a time which submits commands, etc.
-pub struct RconClient {
-    open_stream: TcpStream,
-    //last_id: i32,
-    /// The [`control_packet`] is used to determine wether the end of a possible
-    /// multi-packet response is reached by sending it right after any submit of
-    /// a command and reading back the response ids.
-    ///
-    /// It is important to have its [`pid`] always different then any possible [`last_id`].
-    control_packet: RawPacket,
+///
+/// The protocol itself is handled by a [`ClientSession`](../session/struct.ClientSession.html),
+/// this type adds the blocking I/O. The stream is a `TcpStream` by default; any other
+/// blocking stream implementing [`ReadTimeout`](trait.ReadTimeout.html), e.g. a TLS tunnel or
+/// a test transport, can be used with [`from_stream`](#method.from_stream).
+pub struct RconClient<S = TcpStream> {
+    open_stream: S,
+    session: ClientSession,
+    password: String,
+    text_decoding: TextDecoding,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    /// Set once a command timed out; the rest of its response might still arrive.
+    broken: bool,
+    reconnect: Option<Reconnect<S>>,
+    reconnects: u64,
 }
-impl RconClient {
+
+// The policy of a client reconnecting, and how it opens a new stream.
+struct Reconnect<S> {
+    policy: ReconnectPolicy,
+    connect: Box<dyn FnMut() -> Result<S, RconError> + Send>,
+}
+
+impl<S: Read + Write + ReadTimeout> RconClient<S> {
     /// Submits a command to the open RCON stream. Submit means, that
-    /// it sends the package via stream, followed by the [`control_packet`],
+    /// it sends the package via stream, followed by the control packet,
     /// then waits for returning packets until a response packet with a
-    /// packet id fitting the [`control_packet`] packet id is received.
+    /// packet id fitting the control packet id is received.
     ///
     /// All packets inbetween are considered to be an answer to the provided
-    /// [`RawPacket`] and their values are combined into one string. 
-    pub fn exec<T: Into<String>>(&mut self, command: T) -> io::Result<String> {
-        let command_id = START_ID;
-        let packet =
-            RawPacket::new_exec(command_id, command)
-            .map_err(|e| e.to_io_error())?;
-
-        send_packet(&mut self.open_stream, &packet)?; // send command
-        send_packet(&mut self.open_stream, &self.control_packet)?; // send control_packet
-
-        let mut response_str: String;
-        let response =
-            recv_packet(&mut self.open_stream)?;
-        response_str = response.pbody;
-        
-
-        // recv responses while its not the response from the control_packet.
-        while {
-            let control =
-                recv_packet(&mut self.open_stream)?;
-            if control.pid != CONTROL_ID {
-                response_str = response_str + &control.pbody;
-                true
-            } else {
-                false
+    /// command and their values are combined into one string.
+    ///
+    /// Fails with `RconError::ResponseTooLarge` if the response exceeds the size set by
+    /// [`set_max_response_size`](#method.set_max_response_size); the connection can still
+    /// be used afterwards.
+    ///
+    /// Fails with `RconError::Timeout` if a read or write exceeds the timeouts set by
+    /// [`set_read_timeout`](#method.set_read_timeout) and
+    /// [`set_write_timeout`](#method.set_write_timeout). Without timeouts, this blocks until
+    /// the server answers.
+    pub fn exec<T: Into<String>>(&mut self, command: T) -> Result<String, RconError> {
+        self.exec_until(command, None)
+    }
+
+    /// Like [`exec`](#method.exec), but fails with `RconError::Timeout` if the full response
+    /// did not arrive within `timeout`.
+    ///
+    /// After a timeout, the rest of the response might still arrive. Every request has an id
+    /// of its own, so such late packets are recognized and skipped (see
+    /// [`stale_packets`](#method.stale_packets)), and the client can be used right away.
+    ///
+    /// A write which timed out might have sent half a packet though, which leaves the server
+    /// out of sync. The client is marked as broken then: every further command fails with
+    /// `RconError::Broken`, and the connection needs to be reopened. With a
+    /// [`ReconnectPolicy`](../reconnect/struct.ReconnectPolicy.html) set, the next command
+    /// reconnects instead, and fails with `RconError::Disconnected` if that does not work.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{client::*, error::*, server::*};
+    /// use std::{thread, time::Duration};
+    ///
+    /// let server =
+    ///     RconServer::bind("127.0.0.1:0", "mypass", |request: &CommandRequest| {
+    ///         if request.command == "slow" {
+    ///             thread::sleep(Duration::from_millis(500));
+    ///         }
+    ///         "done".to_string()
+    ///     })
+    ///     .unwrap();
+    /// let addr = server.local_addr().unwrap();
+    /// thread::spawn(move || server.serve());
+    ///
+    /// let mut rcon = RconClient::open(addr.to_string(), "mypass", None::<String>, None).unwrap();
+    /// assert_eq!(rcon.exec_with_timeout("fast", Duration::from_millis(200)).unwrap(), "done");
+    ///
+    /// assert!(matches!(rcon.exec_with_timeout("slow", Duration::from_millis(100)),
+    ///                  Err(RconError::Timeout)));
+    ///
+    /// // the late response to `slow` is skipped.
+    /// assert_eq!(rcon.exec("fast").unwrap(), "done");
+    /// assert!(rcon.stale_packets() > 0);
+    /// assert!(!rcon.is_broken());
+    ///
+    /// // a zero timeout would let every read fail.
+    /// assert!(rcon.set_read_timeout(Some(Duration::ZERO)).is_err());
+    /// ```
+    pub fn exec_with_timeout<T: Into<String>>(&mut self,
+                                              command: T,
+                                              timeout: Duration) -> Result<String, RconError> {
+        self.exec_until(command, Some(Instant::now() + timeout))
+    }
+
+    /// Executes all `commands` in one go: their packets are written at once, followed by a
+    /// single control packet, and the responses, told apart by their ids, are returned in
+    /// the order of the commands. A batch of read-only commands hence takes about one round
+    /// trip instead of one per command.
+    ///
+    /// Errors of a single response, `RconError::ResponseTooLarge` or `RconError::Encoding`,
+    /// are reported in its place; anything else fails the whole batch, like for
+    /// [`exec`](#method.exec). After a lost connection, the batch is only sent again if the
+    /// reconnect policy retries every command of it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{client::*, server::*};
+    /// use std::thread;
+    ///
+    /// let server =
+    ///     RconServer::bind("127.0.0.1:0", "mypass", |request: &CommandRequest| {
+    ///         format!("ran {}", request.command)
+    ///     })
+    ///     .unwrap();
+    /// let addr = server.local_addr().unwrap();
+    /// thread::spawn(move || server.serve());
+    ///
+    /// let mut rcon = RconClient::open(addr.to_string(), "mypass", None::<String>, None).unwrap();
+    /// let responses = rcon.exec_many(&["status", "list", "time"]).unwrap();
+    /// let responses: Vec<String> = responses.into_iter().map(Result::unwrap).collect();
+    /// assert_eq!(responses, ["ran status", "ran list", "ran time"]);
+    /// ```
+    pub fn exec_many<T: AsRef<str>>(&mut self, commands: &[T]) -> Result<Vec<Result<String, RconError>>, RconError> {
+        let commands: Vec<String> = commands.iter().map(|command| command.as_ref().to_string()).collect();
+        self.exec_batch(&commands, None)
+    }
+
+    fn exec_until<T: Into<String>>(&mut self,
+                                   command: T,
+                                   deadline: Option<Instant>) -> Result<String, RconError> {
+        let mut responses = self.exec_batch(&[command.into()], deadline)?;
+        responses.pop().expect("a response per command")
+    }
+
+    fn exec_batch(&mut self,
+                  commands: &[String],
+                  deadline: Option<Instant>) -> Result<Vec<Result<String, RconError>>, RconError> {
+        if self.broken {
+            if self.reconnect.is_none() {
+                return Err(RconError::Broken);
             }
-        } {}
+            self.reconnect()?;
+        }
 
-        //self.last_id = self.last_id + 1;
+        match self.exec_once(commands, deadline) {
+            Err(e) if self.reconnect.is_some() && is_connection_lost(&e) => {
+                self.broken = true;
+                let retry = self.reconnect.as_ref().is_some_and(
+                    |r| commands.iter().all(|command| r.policy.should_retry(command)));
+                if !retry {
+                    // the next command reconnects.
+                    return Err(e);
+                }
+                self.reconnect()?;
+                self.exec_once(commands, deadline)
+            },
+            result => result,
+        }
+    }
 
-        Ok(response_str)
+    fn exec_once(&mut self,
+                 commands: &[String],
+                 deadline: Option<Instant>) -> Result<Vec<Result<String, RconError>>, RconError> {
+        let command_ids =
+            self.session.exec_many(commands)?;
+        match self.receive_responses(&command_ids, deadline) {
+            Err(RconError::Timeout) => {
+                // late packets of the commands are skipped as stale.
+                self.session.cancel();
+                Err(RconError::Timeout)
+            },
+            Err(RconError::Io(e)) if is_timeout(&e) => {
+                self.broken = true;
+                Err(RconError::Timeout)
+            },
+            result => result,
+        }
+    }
 
+    fn receive_responses(&mut self,
+                         command_ids: &[i32],
+                         deadline: Option<Instant>) -> Result<Vec<Result<String, RconError>>, RconError> {
+        flush_session(&mut self.open_stream, &mut self.session)?;
+
+        let mut responses: Vec<Option<Result<String, RconError>>> = command_ids.iter().map(|_| None).collect();
+        while responses.iter().any(Option::is_none) {
+            let (id, response) = match next_event(&mut self.open_stream, &mut self.session, self.read_timeout, deadline)? {
+                SessionEvent::ResponseComplete { id, body } =>
+                    (id, self.text_decoding.decode(body).map_err(RconError::Encoding)),
+                SessionEvent::ResponseTooLarge { id } =>
+                    (id, Err(RconError::ResponseTooLarge)),
+                _ => continue,
+            };
+            if let Some(index) = command_ids.iter().position(|&command_id| command_id == id) {
+                responses[index] = Some(response);
+            }
+        }
+        Ok(responses.into_iter().flatten().collect())
     }
 
+    // Opens a new stream and authenticates on it, as often as the policy allows.
+    fn reconnect(&mut self) -> Result<(), RconError> {
+        let reconnect = self.reconnect.as_mut().expect("only called with a reconnect policy");
+
+        let mut attempt = 0;
+        loop {
+            thread::sleep(reconnect.policy.pause(attempt));
+
+            let error = match (reconnect.connect)() {
+                Ok(mut stream) => {
+                    self.session.reset();
+                    let authenticated = match stream.set_write_timeout(self.write_timeout) {
+                        Ok(()) => handshake(&mut stream, &mut self.session, &self.password, self.read_timeout, None),
+                        Err(e) => Err(e.into()),
+                    };
+                    match authenticated {
+                        Ok(()) => {
+                            self.open_stream = stream;
+                            self.broken = false;
+                            self.reconnects += 1;
+                            return Ok(());
+                        },
+                        // trying again does not fix the password.
+                        Err(RconError::AuthFailed) => return Err(RconError::AuthFailed),
+                        Err(e) => e,
+                    }
+                },
+                Err(e) => e,
+            };
+
+            attempt += 1;
+            if attempt >= reconnect.policy.max_attempts {
+                return Err(RconError::Disconnected(Box::new(error)));
+            }
+        }
+    }
+
+    /// Lets the client reconnect with `connect` once the connection got lost, as `policy`
+    /// says. See the [`reconnect`](../reconnect/index.html) module. For a `TcpStream`,
+    /// [`set_reconnect_policy`](#method.set_reconnect_policy) reconnects to the same address.
+    pub fn set_reconnect<F>(&mut self, policy: ReconnectPolicy, connect: F)
+        where F: FnMut() -> Result<S, RconError> + Send + 'static {
+        self.reconnect = Some(Reconnect { policy, connect: Box::new(connect) });
+    }
+
+    /// The number of times the client reconnected so far.
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+
+    /// Returns `true` once the write of a command timed out, see
+    /// [`exec_with_timeout`](#method.exec_with_timeout), or the connection got lost while
+    /// the client reconnects. A client which reconnects does so on the next command.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// The number of packets skipped so far since they answered an earlier command, e.g.
+    /// one which timed out.
+    pub fn stale_packets(&self) -> u64 {
+        self.session.stale_packets()
+    }
+
+    /// Lets every read of `exec` fail with `RconError::Timeout` once the server did not send
+    /// anything for `timeout`. `None` (the default) means reads wait forever. See
+    /// [`exec_with_timeout`](#method.exec_with_timeout) for the state of the client after a
+    /// timeout.
+    ///
+    /// Fails with `RconError::Io` of kind `InvalidInput` for a zero duration.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), RconError> {
+        check_timeout(timeout)?;
+        self.read_timeout = timeout;
+        Ok(())
+    }
+
+    /// Lets writes fail with `RconError::Timeout` once they block for `timeout`, e.g. since
+    /// the server does not read anymore. `None` (the default) means writes wait forever. A
+    /// timed out write marks the client as broken.
+    ///
+    /// Fails with `RconError::Io` of kind `InvalidInput` for a zero duration, or if the stream
+    /// rejects the timeout.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> Result<(), RconError> {
+        check_timeout(timeout)?;
+        self.open_stream.set_write_timeout(timeout)?;
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// Probes which termination strategy the server supports, see the
+    /// [`probe`](../probe/index.html) module, and uses it for every following command. The
+    /// returned profile can be cached and passed to
+    /// [`open_with_strategy`](#method.open_with_strategy) on the next connection.
+    ///
+    /// Depending on the server, this takes up to a `timeout` of `probe` per candidate.
+    pub fn probe(&mut self, probe: &Probe) -> Result<ServerProfile, RconError> {
+        self.session.probe(probe)?;
+
+        loop {
+            if let SessionEvent::Probed(profile) = next_event(&mut self.open_stream, &mut self.session,
+                                                             self.read_timeout, None)? {
+                return Ok(profile);
+            }
+        }
+    }
+
+    /// Selects how `exec` decodes responses into text. The default is strict UTF-8
+    /// (`TextDecoding::Utf8`), where a response with invalid UTF-8 fails with
+    /// `RconError::Encoding`. Servers which send e.g. Latin-1 player names are better
+    /// handled with `TextDecoding::Latin1` or `TextDecoding::Utf8Lossy`.
+    pub fn set_text_decoding(&mut self, text_decoding: TextDecoding) {
+        self.text_decoding = text_decoding;
+    }
+
+    /// Limits the size of responses `exec` accepts, to protect against servers sending
+    /// endless responses. `None` (the default) means no limit.
+    pub fn set_max_response_size(&mut self, max_response_size: Option<usize>) {
+        self.session.set_max_response_size(max_response_size);
+    }
+
+    /// The underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.open_stream
+    }
+
+    /// The underlying stream. Reading or writing it directly confuses the client.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.open_stream
+    }
+
+    /// Authenticates with `pass` on an already connected `stream`. `safe_command` works as
+    /// for [`open`](#method.open); a `timeout` fails the authentication with
+    /// `RconError::Timeout` if the server does not answer in time.
+    ///
+    /// A wrong password is reported as `RconError::AuthFailed`.
+    pub fn from_stream<P: Into<String>,
+                       C: Into<String>>(stream: S,
+                                        pass: P,
+                                        safe_command: Option<C>,
+                                        timeout: Option<Duration>) -> Result<RconClient<S>, RconError> {
+        RconClient::from_stream_with_strategy(stream, pass, default_strategy(safe_command)?, timeout)
+    }
+
+    /// Like [`from_stream`](#method.from_stream), but finds the end of responses with
+    /// `strategy`.
+    pub fn from_stream_with_strategy<P: Into<String>,
+                                     T: TerminationStrategy + 'static>(stream: S,
+                                                                       pass: P,
+                                                                       strategy: T,
+                                                                       timeout: Option<Duration>) -> Result<RconClient<S>, RconError> {
+        RconClient::authenticate(stream, ClientSession::with_strategy(strategy), pass, timeout)
+    }
+
+    /// Like [`from_stream`](#method.from_stream), but for a server speaking `dialect`.
+    pub fn from_stream_dialect<P: Into<String>>(stream: S,
+                                                pass: P,
+                                                dialect: Dialect,
+                                                timeout: Option<Duration>) -> Result<RconClient<S>, RconError> {
+        RconClient::authenticate(stream, ClientSession::with_dialect(dialect), pass, timeout)
+    }
+
+    /// Like [`from_stream`](#method.from_stream), but for a server probed before. See
+    /// [`open_with_profile`](#method.open_with_profile).
+    pub fn from_stream_with_profile<P: Into<String>>(stream: S,
+                                                     pass: P,
+                                                     profile: &ServerProfile,
+                                                     timeout: Option<Duration>) -> Result<RconClient<S>, RconError> {
+        RconClient::authenticate(stream, ClientSession::with_profile(profile)?, pass, timeout)
+    }
+
+    fn authenticate<P: Into<String>>(mut stream: S,
+                                     mut session: ClientSession,
+                                     pass: P,
+                                     timeout: Option<Duration>) -> Result<RconClient<S>, RconError> {
+        let password = pass.into();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        handshake(&mut stream, &mut session, &password, None, deadline)?;
+
+        Ok( RconClient { open_stream: stream,
+                         session,
+                         password,
+                         text_decoding: TextDecoding::default(),
+                         read_timeout: None,
+                         write_timeout: None,
+                         broken: false,
+                         reconnect: None,
+                         reconnects: 0 })
+    }
+}
+
+impl RconClient {
 
     /// Opens up a connection to an RCON server by connection via TCP/IP and authenticated
     /// with provided `pass`.
@@ -209,61 +645,129 @@ impl RconClient {
     /// in this issue there.)
     ///
     /// As a last parameter a `timeout` can be specified to let the function return with an error
-    /// after a certain number of seconds while no connection can be established.
+    /// after a certain number of seconds while no connection can be established. It bounds the
+    /// wait for the answer to the authentication as well, with `RconError::Timeout`.
+    ///
+    /// A wrong password is reported as `RconError::AuthFailed`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{client::*, error::*};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// // connections are established, but the server never answers.
+    /// let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let addr = silent.local_addr().unwrap();
+    ///
+    /// assert!(matches!(RconClient::open(addr.to_string(), "mypass", None::<String>,
+    ///                                   Some(Duration::from_millis(100))),
+    ///                  Err(RconError::Timeout)));
+    /// ```
     pub fn open<A: Into<String>,
                 P: Into<String>,
                 C: Into<String>>(addr: A,
                                  pass: P,
                                  safe_command: Option<C>,
-                                 timeout: Option<Duration>) -> io::Result<RconClient> {
-        // building address:
-        let s_addr: String = addr.into();
-        let sock_addr: SocketAddr =
-            s_addr.parse().map_err(|_|
-                                   Error::new(ErrorKind::Other,
-                                              format!("cannot parse internet address.")))?;
-        // building package and data:
-        let auth_packet =
-            RawPacket::new(START_ID, 3, pass)
-            .map_err(|e|
-                     Error::new(ErrorKind::Other,
-                                format!("auth packet creation error: '{}'", e)))?;
-
-        println!("Connection to rcon server.");
-        //connect:
-        let mut stream = {
-            if let Some(dur) = timeout {
-                TcpStream::connect_timeout(&sock_addr, dur)?
-            } else {
-                TcpStream::connect(&sock_addr)?
-            }
-        };
+                                 timeout: Option<Duration>) -> Result<RconClient, RconError> {
+        RconClient::open_with_strategy(addr, pass, default_strategy(safe_command)?, timeout)
+    }
 
-        // sending auth 
-        send_packet(&mut stream, &auth_packet)?;
-        // ... and recv result:
-        let auth =
-            recv_auth(&mut stream, START_ID)?;
-        // this ^^ function is somewhat a hack to satisfy sloppy(?) written servers.
-
-        if auth {
-            // either use the `safe_command` or the `RESPONSE_VALUE` trick.
-            let control_packet = {
-                if let Some(cmd) = safe_command {
-                    RawPacket::new_exec(CONTROL_ID, cmd)
-                        .map_err(|e| e.to_io_error())?
-                } else {
-                    RawPacket::new_response_value(CONTROL_ID, "")
-                        .map_err(|e| e.to_io_error())?
-                }
-            };
+    /// Like [`open`](#method.open), but finds the end of responses with `strategy` instead
+    /// of a safe command or the `SERVERDATA_RESPONSE_VALUE` trick. See the
+    /// [`termination`](../termination/index.html) module for the available strategies.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use facio::{client::*, termination::*};
+    /// use std::time::Duration;
+    ///
+    /// // Minecraft ignores the `SERVERDATA_RESPONSE_VALUE` trick, but answers packets of
+    /// // unknown type.
+    /// let mut rcon =
+    ///     RconClient::open_with_strategy("127.0.0.1:25575", "mypass",
+    ///                                    InvalidTypeProbe::default(), None).unwrap();
+    /// println!("{}", rcon.exec("list").unwrap());
+    /// ```
+    pub fn open_with_strategy<A: Into<String>,
+                              P: Into<String>,
+                              T: TerminationStrategy + 'static>(addr: A,
+                                                                pass: P,
+                                                                strategy: T,
+                                                                timeout: Option<Duration>) -> Result<RconClient, RconError> {
+        RconClient::from_stream_with_strategy(connect(addr, timeout)?, pass, strategy, timeout)
+    }
 
-            Ok( RconClient { open_stream: stream, control_packet })
+    /// Like [`open`](#method.open), but for a server speaking `dialect`: the command length,
+    /// the packet size, the auth reply and the way to find the end of responses are those of
+    /// the game. See the [`dialect`](../dialect/index.html) module.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use facio::{client::*, dialect::*};
+    ///
+    /// let mut rcon =
+    ///     RconClient::open_dialect("127.0.0.1:25575", "mypass", Dialect::Minecraft, None).unwrap();
+    /// println!("{}", rcon.exec("list").unwrap());
+    /// ```
+    pub fn open_dialect<A: Into<String>,
+                        P: Into<String>>(addr: A,
+                                         pass: P,
+                                         dialect: Dialect,
+                                         timeout: Option<Duration>) -> Result<RconClient, RconError> {
+        RconClient::from_stream_dialect(connect(addr, timeout)?, pass, dialect, timeout)
+    }
 
+    /// Like [`open`](#method.open), but for a server probed before: the termination strategy
+    /// and the auth reply are those of `profile`, so no probing is needed. See the
+    /// [`probe`](../probe/index.html) module.
+    pub fn open_with_profile<A: Into<String>,
+                             P: Into<String>>(addr: A,
+                                              pass: P,
+                                              profile: &ServerProfile,
+                                              timeout: Option<Duration>) -> Result<RconClient, RconError> {
+        RconClient::from_stream_with_profile(connect(addr, timeout)?, pass, profile, timeout)
+    }
+
+    /// Lets the client reconnect to the same server once the connection got lost, as
+    /// `policy` says. See the [`reconnect`](../reconnect/index.html) module.
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        let addr = self.open_stream.peer_addr().ok();
+        let timeout = policy.connect_timeout;
+        self.set_reconnect(policy, move || {
+            let addr = addr.ok_or_else(
+                || Error::new(ErrorKind::NotConnected, "Address of the server is unknown."))?;
+            connect_to(addr, timeout)
+        });
+    }
+}
+
+// Connects to `addr`, within `timeout` if given.
+fn connect<A: Into<String>>(addr: A, timeout: Option<Duration>) -> Result<TcpStream, RconError> {
+    // building address:
+    let s_addr: String = addr.into();
+    let sock_addr: SocketAddr =
+        s_addr.parse()?;
+
+    connect_to(sock_addr, timeout)
+}
+
+fn connect_to(sock_addr: SocketAddr, timeout: Option<Duration>) -> Result<TcpStream, RconError> {
+    //connect:
+    let stream = {
+        if let Some(dur) = timeout {
+            TcpStream::connect_timeout(&sock_addr, dur)
+                .map_err(|e| match e.kind() {
+                    ErrorKind::TimedOut => RconError::Timeout,
+                    _ => e.into(),
+                })?
         } else {
-            Err(
-                Error::new(ErrorKind::Other,
-                           "Authentication failed. Wrong password."))
+            TcpStream::connect(sock_addr)?
         }
-    }
+    };
+
+    Ok(stream)
 }
@@ -0,0 +1,114 @@
+//! # Codec
+//!
+//! A [`tokio_util::codec`](https://docs.rs/tokio-util/latest/tokio_util/codec/index.html)
+//! `Encoder`/`Decoder` for `RawPacket`, using the same layout as
+//! [`RawPacket::serialize`](../raw_packet/struct.RawPacket.html#method.serialize). Wrapping a
+//! stream in `Framed` with an [`RconCodec`](struct.RconCodec.html) gives a `Stream` and `Sink`
+//! of packets.
+//!
+//! Only available with the `codec` feature enabled.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{raw_packet::*, codec::*};
+//! use bytes::BytesMut;
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = RconCodec::new();
+//! let mut buffer = BytesMut::new();
+//!
+//! let packet = RawPacket::new_exec(3, "status").unwrap();
+//! codec.encode(&packet, &mut buffer).unwrap();
+//!
+//! assert_eq!(codec.decode(&mut buffer).unwrap(), Some(packet));
+//! assert!(buffer.is_empty());
+//! ```
+
+use super::raw_packet::*;
+
+use std::io;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Encodes and decodes `RawPacket`s. See the [module documentation](index.html).
+///
+/// Invalid packets are reported as `PacketDecodeError` wrapped into an `std::io::Error`, just
+/// like [`RawPacket::deserialize`](../raw_packet/struct.RawPacket.html#method.deserialize) does.
+#[derive(Debug, Clone, Copy)]
+pub struct RconCodec {
+    max_frame_size: usize,
+}
+
+impl RconCodec {
+    /// Creates a codec accepting packets up to the maximum size of the protocol,
+    /// i.e. a `psize` of 4096.
+    pub fn new() -> RconCodec {
+        RconCodec { max_frame_size: MAX_PACKET_SIZE as usize }
+    }
+
+    /// Creates a codec which rejects packets with a `psize` larger than `max_frame_size`.
+    ///
+    /// Values above 4096 let the codec decode oversized packets some servers send. Since
+    /// `RawPacket` itself cannot be created with a body larger than the protocol maximum,
+    /// they have no effect on encoding.
+    pub fn with_max_frame_size(max_frame_size: usize) -> RconCodec {
+        RconCodec { max_frame_size }
+    }
+
+    /// The maximum `psize` this codec accepts.
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    fn check_size(&self, psize: i32) -> Result<(), PacketDecodeError> {
+        frame_size_within(psize, self.max_frame_size).map(|_| ())
+    }
+}
+
+impl Default for RconCodec {
+    fn default() -> RconCodec {
+        RconCodec::new()
+    }
+}
+
+impl Decoder for RconCodec {
+    type Item = RawPacket;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<RawPacket>> {
+        let parsed =
+            RawPacketRef::parse_with_max_size(src, self.max_frame_size)?
+            .map(|(packet, length)| (packet.to_packet(), length));
+
+        match parsed {
+            Some((packet, length)) => {
+                src.advance(length);
+                Ok(Some(packet))
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<&RawPacket> for RconCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &RawPacket, dst: &mut BytesMut) -> io::Result<()> {
+        let mut frame = Vec::new();
+        item.serialize_into(&mut frame);
+        self.check_size(frame.len() as i32 - 4)?;
+
+        dst.put_slice(&frame);
+        Ok(())
+    }
+}
+
+impl Encoder<RawPacket> for RconCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: RawPacket, dst: &mut BytesMut) -> io::Result<()> {
+        self.encode(&item, dst)
+    }
+}
@@ -0,0 +1,101 @@
+//! # Decoder
+//!
+//! [`RawPacket::deserialize`](../raw_packet/struct.RawPacket.html#method.deserialize) reads
+//! from a blocking `Read` and needs the whole packet at once. Non-blocking sockets deliver
+//! whatever is available though, which might be half a packet or several packets at once.
+//!
+//! The [`PacketDecoder`](struct.PacketDecoder.html) accepts such chunks of bytes, keeps
+//! partial packets between calls and yields every packet as soon as it is complete.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{raw_packet::*, decoder::*};
+//!
+//! let mut bytes = Vec::new();
+//! RawPacket::new_response_value(1, "first").unwrap().serialize(&mut bytes).unwrap();
+//! RawPacket::new_response_value(2, "second").unwrap().serialize(&mut bytes).unwrap();
+//!
+//! let mut decoder = PacketDecoder::new();
+//!
+//! // only half of the first packet arrived:
+//! decoder.feed(&bytes[..8]);
+//! assert_eq!(decoder.next_packet().unwrap(), None);
+//!
+//! // the rest arrives:
+//! decoder.feed(&bytes[8..]);
+//! assert_eq!(decoder.next_packet().unwrap().unwrap().pid, 1);
+//! assert_eq!(decoder.next_packet().unwrap().unwrap().pid, 2);
+//! assert_eq!(decoder.next_packet().unwrap(), None);
+//! ```
+
+use super::raw_packet::*;
+
+/// Buffers incoming bytes and decodes them into `RawPacket`s. See the
+/// [module documentation](index.html).
+#[derive(Debug)]
+pub struct PacketDecoder {
+    buffer: Vec<u8>,
+    /// Number of bytes at the start of `buffer` which were already decoded.
+    consumed: usize,
+    max_packet_size: usize,
+}
+
+impl PacketDecoder {
+    /// Creates an empty decoder.
+    pub fn new() -> PacketDecoder {
+        PacketDecoder::default()
+    }
+
+    /// Creates an empty decoder which accepts packets with a `psize` up to
+    /// `max_packet_size`, see [`RawPacketRef::parse_with_max_size`](../raw_packet/struct.RawPacketRef.html#method.parse_with_max_size).
+    pub fn with_max_packet_size(max_packet_size: usize) -> PacketDecoder {
+        PacketDecoder { max_packet_size, ..PacketDecoder::default() }
+    }
+
+    /// Appends received bytes to the buffer.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        // drop what was decoded already before growing the buffer.
+        if self.consumed > 0 {
+            self.buffer.drain(..self.consumed);
+            self.consumed = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Decodes the next packet from the buffer. Returns `None` if there is no complete
+    /// packet buffered yet.
+    ///
+    /// Fails if the buffered data cannot be a valid packet (see
+    /// [`RawPacket::deserialize`](../raw_packet/struct.RawPacket.html#method.deserialize) for the
+    /// checks). The decoder should not be used afterwards, since it cannot know where the next
+    /// packet starts.
+    pub fn next_packet(&mut self) -> Result<Option<RawPacket>, PacketDecodeError> {
+        let available = &self.buffer[self.consumed..];
+        match RawPacketRef::parse_with_max_size(available, self.max_packet_size)? {
+            Some((packet, length)) => {
+                let packet = packet.to_packet();
+                self.consumed += length;
+                Ok(Some(packet))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Drops all buffered bytes, e.g. when the connection is replaced.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.consumed = 0;
+    }
+
+    /// Number of buffered bytes which are not decoded yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.consumed
+    }
+}
+
+impl Default for PacketDecoder {
+    fn default() -> PacketDecoder {
+        PacketDecoder { buffer: Vec::new(), consumed: 0, max_packet_size: MAX_PACKET_SIZE as usize }
+    }
+}
@@ -0,0 +1,130 @@
+//! # Dialect
+//!
+//! Every game speaks its own dialect of RCON. They differ in the maximum length of commands,
+//! in whether long responses are split into several packets, in the shape of the auth reply
+//! and in which [termination strategy](../termination/index.html) works. A
+//! [`Dialect`](enum.Dialect.html) bundles these for a known game:
+//!
+//! | Dialect     | Max. command | Splits responses | Auth reply | Termination           |
+//! |-------------|--------------|------------------|------------|-----------------------|
+//! | `Source`    | 4086 bytes   | yes              | double     | `ResponseValueMirror` |
+//! | `Minecraft` | 1446 bytes   | yes              | single     | `InvalidTypeProbe`    |
+//! | `Factorio`  | 4086 bytes   | no               | double     | `SinglePacket`        |
+//! | `Ark`       | 4086 bytes   | no               | single     | `SinglePacket`        |
+//! | `Squad`     | 4086 bytes   | yes              | double     | `ResponseValueMirror` |
+//! | `Palworld`  | 4086 bytes   | no               | single     | `SinglePacket`        |
+//! | `Rust`      | 4086 bytes   | yes              | double     | `ResponseValueMirror` |
+//!
+//! Servers which do not split responses send long ones as a single packet exceeding the
+//! protocol maximum, Minecraft splits at 4096 bytes of body; the clients accept such packets
+//! for these dialects.
+//!
+//! A dialect is used with [`RconClient::open_dialect`](../client/struct.RconClient.html#method.open_dialect)
+//! or [`ClientSession::with_dialect`](../session/struct.ClientSession.html#method.with_dialect).
+//! For a server of an unknown game, see the [`probe`](../probe/index.html) module.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{client::*, dialect::*, raw_packet::*, server::*, session::*};
+//! use std::thread;
+//!
+//! let server =
+//!     RconServer::bind("127.0.0.1:0", "mypass", |_: &CommandRequest| "players: 3".to_string())
+//!     .unwrap();
+//! let addr = server.local_addr().unwrap();
+//! thread::spawn(move || server.serve());
+//!
+//! let mut rcon =
+//!     RconClient::open_dialect(addr.to_string(), "mypass", Dialect::Source, None).unwrap();
+//! assert_eq!(rcon.exec("status").unwrap(), "players: 3");
+//!
+//! // Minecraft rejects commands longer than 1446 bytes, so they are not even sent.
+//! let mut session = ClientSession::with_dialect(Dialect::Minecraft);
+//! assert_eq!(session.exec("say ".to_string() + &"x".repeat(1500)),
+//!            Err(RawPacketCreationError::BodyTooLarge));
+//! ```
+
+use super::raw_packet::*;
+use super::probe::*;
+use super::termination::InvalidTypeProbe;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The largest `psize` accepted from servers which do not split responses.
+const UNSPLIT_PACKET_SIZE: usize = 1 << 20;
+
+/// The RCON dialect of a game. See the [module documentation](index.html).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Dialect {
+    /// Source engine games, e.g. Counter-Strike 2, Team Fortress 2 or Garry's Mod; the
+    /// protocol as the spec describes it.
+    Source,
+    /// Minecraft: Java Edition.
+    Minecraft,
+    /// Factorio.
+    Factorio,
+    /// ARK: Survival Evolved.
+    Ark,
+    /// Squad.
+    Squad,
+    /// Palworld.
+    Palworld,
+    /// Rust, with the legacy RCON instead of WebRCON.
+    Rust,
+}
+
+impl Dialect {
+    /// All known dialects.
+    pub const ALL: [Dialect; 7] = [
+        Dialect::Source, Dialect::Minecraft, Dialect::Factorio, Dialect::Ark,
+        Dialect::Squad, Dialect::Palworld, Dialect::Rust,
+    ];
+
+    /// The longest command, in bytes, the server accepts.
+    pub fn max_command_len(&self) -> usize {
+        match self {
+            Dialect::Minecraft => 1446,
+            _ => MAX_BODY_SIZE as usize,
+        }
+    }
+
+    /// The largest `psize` the server sends.
+    pub fn max_packet_size(&self) -> usize {
+        match self {
+            Dialect::Minecraft => 4096 + MIN_PACKET_SIZE as usize,
+            _ if !self.splits_responses() => UNSPLIT_PACKET_SIZE,
+            _ => MAX_PACKET_SIZE as usize,
+        }
+    }
+
+    /// Whether the server splits long responses into several packets.
+    pub fn splits_responses(&self) -> bool {
+        !matches!(self, Dialect::Factorio | Dialect::Ark | Dialect::Palworld)
+    }
+
+    /// The shape of the auth reply of the server.
+    pub fn auth_reply(&self) -> AuthReply {
+        match self {
+            Dialect::Minecraft | Dialect::Ark | Dialect::Palworld => AuthReply::Single,
+            _ => AuthReply::Double,
+        }
+    }
+
+    /// How the end of responses is found.
+    pub fn termination(&self) -> Termination {
+        match self {
+            Dialect::Minecraft => Termination::InvalidTypeProbe(InvalidTypeProbe::DEFAULT_TYPE),
+            _ if !self.splits_responses() => Termination::SinglePacket,
+            _ => Termination::ResponseValueMirror,
+        }
+    }
+
+    /// The dialect as a [`ServerProfile`](../probe/struct.ServerProfile.html), as probing
+    /// would find it.
+    pub fn profile(&self) -> ServerProfile {
+        ServerProfile { auth_reply: self.auth_reply(), termination: self.termination() }
+    }
+}
@@ -0,0 +1,164 @@
+//! # Errors
+//!
+//! The error types of the client side. Everything which can go wrong while talking to a
+//! server is an [`RconError`](enum.RconError.html), so callers can tell a wrong password from
+//! a network failure without looking at messages.
+
+use super::raw_packet::*;
+
+use std::fmt;
+use std::io;
+use std::net::AddrParseError;
+use std::string::FromUtf8Error;
+
+/// The server sent something which is not allowed at this point of the protocol.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ProtocolError {
+    /// Received data could not be decoded into a packet.
+    Decode(PacketDecodeError),
+    /// The server answered an auth request with two packets, neither of which
+    /// was a `SERVERDATA_AUTH_RESPONSE`, or, if it is known to send a single auth reply,
+    /// with another packet.
+    NoAuthResponse,
+    /// The server sent a packet while no request was pending.
+    UnexpectedPacket { pid: i32 },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Decode(e) =>
+                write!(f, "Cannot decode packet: {}", e),
+            ProtocolError::NoAuthResponse =>
+                write!(f, "No valid authentication protocol by server."),
+            ProtocolError::UnexpectedPacket { pid } =>
+                write!(f, "Unexpected packet from server with id {}.", pid),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtocolError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PacketDecodeError> for ProtocolError {
+    fn from(e: PacketDecodeError) -> ProtocolError {
+        ProtocolError::Decode(e)
+    }
+}
+
+/// The error type of [`RconClient`](../client/struct.RconClient.html) and the other clients.
+#[derive(Debug)]
+pub enum RconError {
+    /// The underlying connection failed.
+    Io(io::Error),
+    /// The server rejected the password.
+    AuthFailed,
+    /// The address to connect to cannot be parsed.
+    InvalidAddress(AddrParseError),
+    /// A packet to be sent cannot be created, e.g. since the command is too long.
+    Packet(RawPacketCreationError),
+    /// The server violated the protocol.
+    Protocol(ProtocolError),
+    /// The server did not answer in time.
+    Timeout,
+    /// Writing a former command timed out, so the connection is out of sync and needs to
+    /// be reopened. A client with a reconnect policy reconnects instead.
+    Broken,
+    /// The connection got lost and reconnecting failed as often as the
+    /// [`ReconnectPolicy`](../reconnect/struct.ReconnectPolicy.html) allows; the error is the
+    /// one of the last attempt. The next command tries to reconnect again.
+    Disconnected(Box<RconError>),
+    /// The response exceeds the configured maximum size.
+    ResponseTooLarge,
+    /// The response cannot be decoded with the configured `TextDecoding`. The error
+    /// gives access to the received bytes.
+    Encoding(FromUtf8Error),
+}
+
+impl fmt::Display for RconError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RconError::Io(e) =>
+                write!(f, "I/O error: {}", e),
+            RconError::AuthFailed =>
+                write!(f, "Authentication failed. Wrong password."),
+            RconError::InvalidAddress(e) =>
+                write!(f, "Cannot parse internet address: {}", e),
+            RconError::Packet(e) =>
+                write!(f, "Cannot create packet: {}", e),
+            RconError::Protocol(e) =>
+                write!(f, "Protocol violation: {}", e),
+            RconError::Timeout =>
+                write!(f, "Server did not answer in time."),
+            RconError::Broken =>
+                write!(f, "Connection is out of sync after a write timed out."),
+            RconError::Disconnected(e) =>
+                write!(f, "Connection lost, reconnecting failed: {}", e),
+            RconError::ResponseTooLarge =>
+                write!(f, "Response exceeds the maximum size."),
+            RconError::Encoding(e) =>
+                write!(f, "Cannot decode response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RconError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RconError::Io(e) => Some(e),
+            RconError::InvalidAddress(e) => Some(e),
+            RconError::Disconnected(e) => Some(e.as_ref()),
+            RconError::Packet(e) => Some(e),
+            RconError::Protocol(e) => Some(e),
+            RconError::Encoding(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RconError {
+    /// I/O errors which carry a `PacketDecodeError` (see
+    /// [`RawPacket::deserialize`](../raw_packet/struct.RawPacket.html#method.deserialize)) become
+    /// a `Protocol` error, all others an `Io` error.
+    fn from(e: io::Error) -> RconError {
+        let decode_error =
+            e.get_ref()
+            .and_then(|inner| inner.downcast_ref::<PacketDecodeError>())
+            .copied();
+
+        match decode_error {
+            Some(decode_error) => RconError::Protocol(ProtocolError::Decode(decode_error)),
+            None => RconError::Io(e),
+        }
+    }
+}
+
+impl From<AddrParseError> for RconError {
+    fn from(e: AddrParseError) -> RconError {
+        RconError::InvalidAddress(e)
+    }
+}
+
+impl From<RawPacketCreationError> for RconError {
+    fn from(e: RawPacketCreationError) -> RconError {
+        RconError::Packet(e)
+    }
+}
+
+impl From<ProtocolError> for RconError {
+    fn from(e: ProtocolError) -> RconError {
+        RconError::Protocol(e)
+    }
+}
+
+impl From<PacketDecodeError> for RconError {
+    fn from(e: PacketDecodeError) -> RconError {
+        RconError::Protocol(ProtocolError::Decode(e))
+    }
+}
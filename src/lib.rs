@@ -6,12 +6,25 @@
 //! [`RawPacket`](raw_packet/struct.RawPacket.html) and a higher-level client type [`RconClient`](client/struct.RconClient.html).
 //!
 //! The `RawPacket` type gives a wrapper around the packets sent through rcon connections
-//! which can be also be used on the server-side of things.
+//! which can be also be used on the server-side of things, as the embeddable
+//! [`RconServer`](server/struct.RconServer.html) does. Its commands can be declared with a
+//! [`CommandRouter`](router/struct.CommandRouter.html), its users checked by an
+//! [`Authenticator`](auth/trait.Authenticator.html). With the `tokio` feature enabled, there is
+//! also an async version of the server, [`AsyncRconServer`](async_server/struct.AsyncRconServer.html).
 //!
 //! The `RconClient` type provides a higher-level entry point for building a RCON client.
+//! With the `tokio` feature enabled, there is also an async version of it,
+//! [`AsyncRconClient`](async_client/struct.AsyncRconClient.html).
+//!
+//! Both clients share the protocol logic implemented by the sans-IO
+//! [`ClientSession`](session/struct.ClientSession.html), which can also be driven by an own event loop.
 //!
 //! After all, there is low-level part in [`facio::ll`](ll/index.html) which provides low-level
-//! functions to send and receive `RawPacket` via a `TcpStream`.
+//! functions to send and receive `RawPacket` via a `TcpStream` or any other stream, or, with the `futures` feature
+//! enabled, via any `futures::io::AsyncRead`/`AsyncWrite`, independent of the executor.
+//! For non-blocking sockets, the [`PacketDecoder`](decoder/struct.PacketDecoder.html) decodes
+//! packets from partial reads. With the `codec` feature enabled, there is a `tokio-util` codec
+//! [`RconCodec`](codec/struct.RconCodec.html) as well.
 
 /// Wrapper around RCON packet byte structure
 pub mod raw_packet;
@@ -19,5 +32,58 @@ pub mod raw_packet;
 /// High-Level RCON client
 pub mod client;
 
+/// Async High-Level RCON client on tokio
+#[cfg(feature = "tokio")]
+pub mod async_client;
+
+/// Incremental decoding of RawPackets from byte chunks
+pub mod decoder;
+
+/// tokio-util codec for RawPackets
+#[cfg(feature = "codec")]
+pub mod codec;
+
+/// Sans-IO RCON client protocol
+pub mod session;
+
+/// End-of-response detection strategies for the clients
+pub mod termination;
+
+/// Server quirk detection for the clients
+pub mod probe;
+
+/// Per-game RCON dialects for the clients
+pub mod dialect;
+
+/// Automatic reconnects of the clients
+pub mod reconnect;
+
+/// Embeddable RCON server
+pub mod server;
+
+/// Async embeddable RCON server on tokio
+#[cfg(feature = "tokio")]
+pub mod async_server;
+
+/// Command routing with argument parsing for the server
+pub mod router;
+
+/// Authentication backends for the server
+pub mod auth;
+
+/// Connection limits and brute-force protection for the server
+pub mod limits;
+
+/// Quirk-emulating mock server for tests
+#[cfg(feature = "test-util")]
+pub mod mock;
+
+/// Fault-injecting transport for tests
+#[cfg(feature = "test-util")]
+pub mod chaos;
+
+/// Error types of the clients
+pub mod error;
+
 /// Low-Level RCON network functions
 pub mod ll;
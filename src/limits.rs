@@ -0,0 +1,363 @@
+//! # Limits
+//!
+//! Protection of an [`RconServer`](../server/struct.RconServer.html) exposed to the internet.
+//! [`Limits`](struct.Limits.html) restrict the number of concurrent connections, ban IP
+//! addresses after too many failed authentications and close connections which do not
+//! authenticate in time or stay idle for too long. A [`ServerObserver`](trait.ServerObserver.html)
+//! is told about connections, authentications, bans and timeouts, e.g. for logging.
+//!
+//! By default, there are no limits at all.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{client::*, error::*, limits::*, server::*};
+//! use std::net::IpAddr;
+//! use std::thread;
+//! use std::time::Duration;
+//!
+//! struct Log;
+//!
+//! impl ServerObserver for Log {
+//!     fn banned(&self, ip: IpAddr, duration: Duration) {
+//!         println!("banned {} for {:?}", ip, duration);
+//!     }
+//! }
+//!
+//! let server =
+//!     RconServer::bind("127.0.0.1:0", "mypass",
+//!                      |request: &CommandRequest| request.command.to_string())
+//!     .unwrap()
+//!     .with_limits(Limits::new()
+//!                      .max_connections(16)
+//!                      .ban_after(2, Duration::from_secs(600))
+//!                      .auth_timeout(Duration::from_secs(5))
+//!                      .idle_timeout(Duration::from_secs(300)))
+//!     .with_observer(Log);
+//! let addr = server.local_addr().unwrap();
+//! thread::spawn(move || server.serve());
+//!
+//! for _ in 0..2 {
+//!     assert!(matches!(RconClient::open(addr.to_string(), "guess", None::<String>, None),
+//!                      Err(RconError::AuthFailed)));
+//! }
+//!
+//! // the address is banned now, even with the right password.
+//! assert!(RconClient::open(addr.to_string(), "mypass", None::<String>, None).is_err());
+//! ```
+
+use super::auth::Identity;
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The limits of a server. See the [module documentation](index.html).
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub(crate) max_connections: Option<usize>,
+    pub(crate) ban: Option<(u32, Duration)>,
+    pub(crate) auth_timeout: Option<Duration>,
+    pub(crate) idle_timeout: Option<Duration>,
+}
+
+impl Limits {
+    /// Creates limits which do not limit anything.
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    /// Rejects connections while `max` connections are open.
+    pub fn max_connections(mut self, max: usize) -> Limits {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Bans the IP address of a client for `duration` after `failures` failed
+    /// authentications. A banned client is disconnected, new connections from its address
+    /// are rejected until the ban expires. Connections from the address which were open
+    /// before are refused any further authentication, even with the right password.
+    ///
+    /// Failures are counted over all connections of an address and forgotten after a
+    /// successful authentication, or after `duration` without another failure.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{client::*, error::*, limits::*, raw_packet::*, server::*};
+    /// use std::net::TcpStream;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let server =
+    ///     RconServer::bind("127.0.0.1:0", "mypass",
+    ///                      |request: &CommandRequest| request.command.to_string())
+    ///     .unwrap()
+    ///     .with_limits(Limits::new().ban_after(1, Duration::from_secs(600)));
+    /// let addr = server.local_addr().unwrap();
+    /// thread::spawn(move || server.serve());
+    ///
+    /// // a connection opened before the ban ...
+    /// let mut open = TcpStream::connect(addr).unwrap();
+    /// assert!(matches!(RconClient::open(addr.to_string(), "guess", None::<String>, None),
+    ///                  Err(RconError::AuthFailed)));
+    ///
+    /// // ... cannot authenticate anymore, and does not lift the ban.
+    /// RawPacket::new_auth(7, "mypass").unwrap().serialize(&mut open).unwrap();
+    /// let _empty = RawPacket::deserialize(&mut open).unwrap();
+    /// assert_eq!(RawPacket::deserialize(&mut open).unwrap().pid, -1);
+    /// assert!(RconClient::open(addr.to_string(), "mypass", None::<String>, None).is_err());
+    /// ```
+    pub fn ban_after(mut self, failures: u32, duration: Duration) -> Limits {
+        self.ban = Some((failures.max(1), duration));
+        self
+    }
+
+    /// Closes connections which did not authenticate within `timeout` after connecting, even
+    /// if they keep sending, but too slowly to complete a packet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{limits::*, server::*};
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    /// use std::thread;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let server =
+    ///     RconServer::bind("127.0.0.1:0", "mypass",
+    ///                      |request: &CommandRequest| request.command.to_string())
+    ///     .unwrap()
+    ///     .with_limits(Limits::new().auth_timeout(Duration::from_millis(300)));
+    /// let addr = server.local_addr().unwrap();
+    /// thread::spawn(move || server.serve());
+    ///
+    /// // a client trickling its auth packet, one byte every 50 milliseconds.
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// let mut writer = stream.try_clone().unwrap();
+    /// thread::spawn(move || {
+    ///     for byte in [30u8, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0].iter().cycle().take(40) {
+    ///         if writer.write_all(&[*byte]).is_err() {
+    ///             break;
+    ///         }
+    ///         thread::sleep(Duration::from_millis(50));
+    ///     }
+    /// });
+    ///
+    /// // the server closes the connection at the deadline anyway.
+    /// let started = Instant::now();
+    /// stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    /// let closed = matches!(stream.read(&mut [0u8; 16]), Ok(0) | Err(_));
+    /// assert!(closed);
+    /// assert!(started.elapsed() < Duration::from_secs(1));
+    /// ```
+    pub fn auth_timeout(mut self, timeout: Duration) -> Limits {
+        self.auth_timeout = Some(timeout);
+        self
+    }
+
+    /// Closes authenticated connections which did not send anything for `timeout`.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Limits {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Why a connection was rejected right after it was accepted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Rejection {
+    /// The maximum number of connections is reached.
+    TooManyConnections,
+    /// The IP address of the client is banned.
+    Banned,
+}
+
+/// Which timeout closed a connection.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeoutKind {
+    /// The client did not authenticate in time.
+    Auth,
+    /// The client was idle for too long.
+    Idle,
+}
+
+/// Is told what happens on a server. Every method does nothing by default, so only the
+/// events of interest need to be implemented.
+///
+/// Like a [`CommandHandler`](../server/trait.CommandHandler.html), an observer might be
+/// called from several threads at once.
+pub trait ServerObserver: Send + Sync {
+    /// A connection was accepted.
+    fn connected(&self, _peer: SocketAddr) {}
+
+    /// A connection was rejected and closed right away.
+    fn rejected(&self, _peer: SocketAddr, _reason: Rejection) {}
+
+    /// A client authenticated as `identity`.
+    fn authenticated(&self, _peer: SocketAddr, _identity: &Identity) {}
+
+    /// A client sent a wrong password; `failures` counts the failures of its IP address
+    /// since its last successful authentication. Failures are forgotten after the `duration`
+    /// of [`ban_after`](struct.Limits.html#method.ban_after) without another one, or after
+    /// 10 minutes without bans.
+    fn auth_failed(&self, _peer: SocketAddr, _failures: u32) {}
+
+    /// The IP address `ip` was banned for `duration`.
+    fn banned(&self, _ip: IpAddr, _duration: Duration) {}
+
+    /// A connection was closed by a timeout.
+    fn timed_out(&self, _peer: SocketAddr, _kind: TimeoutKind) {}
+
+    /// A connection was closed.
+    fn disconnected(&self, _peer: SocketAddr) {}
+
+    /// Accepting a connection failed with `error`, e.g. because the client reset the
+    /// connection during the handshake or the process ran out of file descriptors. The
+    /// server goes on accepting.
+    fn accept_failed(&self, _error: &io::Error) {}
+}
+
+/// Observes nothing.
+impl ServerObserver for () {}
+
+// How long failures are remembered for the observer if there are no bans.
+const FORGET_FAILURES_AFTER: Duration = Duration::from_secs(600);
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    banned_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct GateState {
+    connections: usize,
+    failures: HashMap<IpAddr, Failures>,
+}
+
+impl GateState {
+    fn is_banned(&self, ip: IpAddr, now: Instant) -> bool {
+        self.failures.get(&ip)
+            .and_then(|failures| failures.banned_until)
+            .is_some_and(|until| until > now)
+    }
+}
+
+/// Enforces the limits of a server, shared by all its connections.
+pub(crate) struct Gatekeeper {
+    pub(crate) limits: Limits,
+    pub(crate) observer: Arc<dyn ServerObserver>,
+    state: Mutex<GateState>,
+}
+
+impl Gatekeeper {
+    pub(crate) fn new(limits: Limits, observer: Arc<dyn ServerObserver>) -> Gatekeeper {
+        Gatekeeper { limits, observer, state: Mutex::new(GateState::default()) }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, GateState> {
+        // the state is only changed by plain counting, so it is usable after a panic.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Admits a new connection from `peer`, which counts until the permit is dropped.
+    pub(crate) fn admit(self: &Arc<Self>, peer: SocketAddr) -> Option<Permit> {
+        let rejection = {
+            let mut state = self.state();
+
+            if state.is_banned(peer.ip(), Instant::now()) {
+                Some(Rejection::Banned)
+            } else if self.limits.max_connections.is_some_and(|max| state.connections >= max) {
+                Some(Rejection::TooManyConnections)
+            } else {
+                state.connections += 1;
+                None
+            }
+        };
+
+        match rejection {
+            Some(reason) => {
+                self.observer.rejected(peer, reason);
+                None
+            },
+            None => {
+                self.observer.connected(peer);
+                Some(Permit { gate: Arc::clone(self), peer })
+            },
+        }
+    }
+
+    /// Whether `ip` is banned right now; connections opened before the ban must not
+    /// authenticate anymore either.
+    pub(crate) fn is_banned(&self, ip: IpAddr) -> bool {
+        self.state().is_banned(ip, Instant::now())
+    }
+
+    pub(crate) fn auth_succeeded(&self, peer: SocketAddr, identity: &Identity) {
+        {
+            let mut state = self.state();
+            // a success of an address does not lift its ban.
+            if !state.is_banned(peer.ip(), Instant::now()) {
+                state.failures.remove(&peer.ip());
+            }
+        }
+        self.observer.authenticated(peer, identity);
+    }
+
+    /// Counts a failed authentication of `peer`; returns `true` if its address is banned now.
+    pub(crate) fn auth_failed(&self, peer: SocketAddr) -> bool {
+        let (count, ban) = {
+            let mut state = self.state();
+            let now = Instant::now();
+            let forget_after = self.limits.ban.map_or(FORGET_FAILURES_AFTER, |(_, duration)| duration);
+
+            state.failures.retain(|_, failures| match failures.banned_until {
+                Some(until) => until > now,
+                None => now.duration_since(failures.last) < forget_after,
+            });
+
+            let failures =
+                state.failures.entry(peer.ip())
+                .or_insert(Failures { count: 0, last: now, banned_until: None });
+            failures.count += 1;
+            failures.last = now;
+
+            let ban = self.limits.ban.filter(|&(max, _)| failures.count >= max);
+            if let Some((_, duration)) = ban {
+                failures.banned_until = Some(now + duration);
+            }
+            (failures.count, ban)
+        };
+
+        self.observer.auth_failed(peer, count);
+        match ban {
+            Some((_, duration)) => {
+                self.observer.banned(peer.ip(), duration);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// An admitted connection; dropping it closes the connection for the limits.
+pub(crate) struct Permit {
+    gate: Arc<Gatekeeper>,
+    peer: SocketAddr,
+}
+
+impl Permit {
+    pub(crate) fn gate(&self) -> &Arc<Gatekeeper> {
+        &self.gate
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.gate.state().connections -= 1;
+        self.gate.observer.disconnected(self.peer);
+    }
+}
@@ -1,14 +1,44 @@
-use std::net::TcpStream;
 use super::raw_packet::*;
 use std::io;
+use std::io::{Read, Write};
 
-/// Uses the `Write` of `TcpStream` to send a packet.
-pub fn send_packet(stream: &mut TcpStream, packet: &RawPacket) -> io::Result<()> {
+/// Uses the `Write` of a stream, usually a `TcpStream`, to send a packet.
+pub fn send_packet<W: Write>(stream: &mut W, packet: &RawPacket) -> io::Result<()> {
     packet.serialize(stream)
 }
 
-/// Uses the `Read` of `TcpStream` to receive a packet.
-pub fn recv_packet(stream: &mut TcpStream) -> io::Result<RawPacket> {
+/// Sends several packets with a single write, e.g. a command together with a control
+/// packet. The packets are encoded into one buffer first, so they leave in as few
+/// segments as possible instead of one small segment per packet.
+pub fn send_packets<W: Write>(stream: &mut W, packets: &[RawPacket]) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    for packet in packets {
+        packet.serialize_into(&mut buffer);
+    }
+
+    stream.write_all(&buffer)?;
+    stream.flush()
+}
+
+/// Uses the `Read` of a stream, usually a `TcpStream`, to receive a packet.
+pub fn recv_packet<R: Read>(stream: &mut R) -> io::Result<RawPacket> {
     RawPacket::deserialize(stream)
 }
 
+/// Async counterpart of `send_packet` for any `futures::io::AsyncWrite`.
+///
+/// Only available with the `futures` feature enabled.
+#[cfg(feature = "futures")]
+pub async fn send_packet_async<T>(stream: &mut T, packet: &RawPacket) -> io::Result<()>
+    where T: futures::io::AsyncWrite + Unpin {
+    packet.serialize_async(stream).await
+}
+
+/// Async counterpart of `recv_packet` for any `futures::io::AsyncRead`.
+///
+/// Only available with the `futures` feature enabled.
+#[cfg(feature = "futures")]
+pub async fn recv_packet_async<T>(stream: &mut T) -> io::Result<RawPacket>
+    where T: futures::io::AsyncRead + Unpin {
+    RawPacket::deserialize_async(stream).await
+}
@@ -0,0 +1,272 @@
+//! # Mock Server
+//!
+//! An in-process RCON server for tests, which emulates the quirks of real servers. A
+//! [`MockServer`](struct.MockServer.html) answers scripted commands and can be switched to
+//! misbehave the ways the [`client`](../client/index.html) module documentation warns
+//! about, see [`Quirk`](enum.Quirk.html). This way, client code can be tested against every
+//! server behaviour without running a game server.
+//!
+//! Only available with the `test-util` feature enabled.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{client::*, mock::*};
+//!
+//! let mock = MockServer::new("mypass")
+//!     .respond("status", "players: 3")
+//!     .quirk(Quirk::SingleAuthReply)
+//!     .quirk(Quirk::SplitResponsesAt(4))
+//!     .start()
+//!     .unwrap();
+//!
+//! let mut rcon =
+//!     RconClient::open(mock.addr().to_string(), "mypass", Some("echo"), None).unwrap();
+//! assert_eq!(rcon.exec("status").unwrap(), "players: 3");
+//!
+//! // unscripted commands are answered with an empty response.
+//! assert_eq!(rcon.exec("kick troll").unwrap(), "");
+//!
+//! assert_eq!(mock.commands(), vec!["status", "echo", "kick troll", "echo"]);
+//! ```
+//!
+//! Quirks are what [probing](../probe/index.html) detects:
+//!
+//! ```
+//! use facio::{client::*, mock::*, probe::*};
+//! use std::time::Duration;
+//!
+//! // a server behaving like Minecraft.
+//! let mock = MockServer::new("mypass")
+//!     .respond("list", "There are 0 players online")
+//!     .quirk(Quirk::SingleAuthReply)
+//!     .quirk(Quirk::IgnoreEmptyResponseValue)
+//!     .start()
+//!     .unwrap();
+//!
+//! let mut rcon =
+//!     RconClient::open(mock.addr().to_string(), "mypass", None::<String>, None).unwrap();
+//! let profile = rcon.probe(&Probe::new().timeout(Duration::from_millis(200))).unwrap();
+//! assert_eq!(profile.auth_reply, AuthReply::Single);
+//! assert_eq!(profile.termination, Termination::InvalidTypeProbe(0xc8));
+//! assert_eq!(rcon.exec("list").unwrap(), "There are 0 players online");
+//! ```
+
+use super::raw_packet::*;
+use super::server::AUTH_FAILED_ID;
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A deviation from the spec a [`MockServer`](struct.MockServer.html) can emulate.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Quirk {
+    /// Answers an auth request with the `SERVERDATA_AUTH_RESPONSE` only, without the empty
+    /// `SERVERDATA_RESPONSE_VALUE` before.
+    SingleAuthReply,
+    /// Does not answer empty `SERVERDATA_RESPONSE_VALUE` packets at all, like Minecraft.
+    /// Clients relying on the `SERVERDATA_RESPONSE_VALUE` trick wait forever.
+    IgnoreEmptyResponseValue,
+    /// Mirrors empty `SERVERDATA_RESPONSE_VALUE` packets, but without the marker packet
+    /// the spec describes.
+    NoResponseValueMarker,
+    /// Splits responses into packets with bodies of at most this many bytes, regardless of
+    /// UTF-8 character boundaries.
+    SplitResponsesAt(usize),
+    /// Waits this long before every reply.
+    Delay(Duration),
+    /// Closes the connection instead of answering, once this many commands were answered
+    /// on it.
+    DropAfterCommands(usize),
+}
+
+/// The configuration of a mock server. See the [module documentation](index.html).
+#[derive(Debug, Clone)]
+pub struct MockServer {
+    password: String,
+    responses: HashMap<String, String>,
+    quirks: Vec<Quirk>,
+}
+
+impl MockServer {
+    /// Creates a mock server accepting `password`, which follows the spec and answers every
+    /// command with an empty response.
+    pub fn new<P: Into<String>>(password: P) -> MockServer {
+        MockServer { password: password.into(), responses: HashMap::new(), quirks: Vec::new() }
+    }
+
+    /// Answers `command` with `response`.
+    pub fn respond<C: Into<String>, R: Into<String>>(mut self, command: C, response: R) -> MockServer {
+        self.responses.insert(command.into(), response.into());
+        self
+    }
+
+    /// Enables `quirk`. Quirks with a value replace former ones of the same kind.
+    pub fn quirk(mut self, quirk: Quirk) -> MockServer {
+        self.quirks.retain(|q| std::mem::discriminant(q) != std::mem::discriminant(&quirk));
+        self.quirks.push(quirk);
+        self
+    }
+
+    fn has(&self, quirk: &Quirk) -> bool {
+        self.quirks.contains(quirk)
+    }
+
+    fn split_at(&self) -> usize {
+        self.quirks.iter()
+            .find_map(|q| match q { Quirk::SplitResponsesAt(size) => Some(*size), _ => None })
+            .unwrap_or(MAX_BODY_SIZE as usize)
+            .clamp(1, MAX_BODY_SIZE as usize)
+    }
+
+    fn delay(&self) -> Option<Duration> {
+        self.quirks.iter()
+            .find_map(|q| match q { Quirk::Delay(delay) => Some(*delay), _ => None })
+    }
+
+    fn drop_after(&self) -> Option<usize> {
+        self.quirks.iter()
+            .find_map(|q| match q { Quirk::DropAfterCommands(n) => Some(*n), _ => None })
+    }
+
+    /// Binds to a free port on `127.0.0.1` and serves connections in the background, until
+    /// the returned [`RunningMock`](struct.RunningMock.html) is dropped.
+    pub fn start(self) -> io::Result<RunningMock> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let commands = Arc::new(Mutex::new(Vec::new()));
+
+        let config = Arc::new(self);
+        let accept_stopped = Arc::clone(&stopped);
+        let accept_commands = Arc::clone(&commands);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    return;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let config = Arc::clone(&config);
+                let commands = Arc::clone(&accept_commands);
+                thread::spawn(move || {
+                    let _ = serve_connection(stream, &config, &commands);
+                });
+            }
+        });
+
+        Ok(RunningMock { addr, stopped, commands })
+    }
+}
+
+fn serve_connection(mut stream: TcpStream,
+                    config: &MockServer,
+                    commands: &Mutex<Vec<String>>) -> io::Result<()> {
+    let mut authenticated = false;
+    let mut answered = 0;
+
+    loop {
+        let packet = RawPacket::deserialize(&mut stream)?;
+
+        let replies = match packet.request_type() {
+            Some(PacketType::RequestAuth) => {
+                authenticated = packet.pbody == config.password.as_bytes();
+                let auth_id = if authenticated { packet.pid } else { AUTH_FAILED_ID };
+
+                let mut replies = Vec::new();
+                if !config.has(&Quirk::SingleAuthReply) {
+                    replies.push(packet_with(packet.pid, ""));
+                }
+                replies.push(RawPacket::new_response_auth(auth_id, "")
+                                 .expect("empty packet is valid"));
+                replies
+            },
+            Some(PacketType::RequestExecCommand) if authenticated => {
+                if config.drop_after().is_some_and(|n| answered >= n) {
+                    return Ok(());
+                }
+                answered += 1;
+
+                let command = packet.body_utf8_lossy().into_owned();
+                let response = config.responses.get(&command).cloned().unwrap_or_default();
+                commands.lock().unwrap_or_else(|e| e.into_inner()).push(command);
+
+                let mut replies: Vec<RawPacket> =
+                    response.as_bytes()
+                    .chunks(config.split_at())
+                    .map(|body| packet_with(packet.pid, body))
+                    .collect();
+                if replies.is_empty() {
+                    replies.push(packet_with(packet.pid, ""));
+                }
+                replies
+            },
+            Some(PacketType::RequestExecCommand) =>
+                return Ok(()),
+            _ if packet.response_type() == Some(PacketType::ResponseValue)
+                && packet.pbody.is_empty() => {
+                if config.has(&Quirk::IgnoreEmptyResponseValue) {
+                    continue;
+                }
+                let mut replies = vec![packet_with(packet.pid, "")];
+                if !config.has(&Quirk::NoResponseValueMarker) {
+                    replies.push(packet_with(packet.pid, RESPONSE_VALUE_MARKER));
+                }
+                replies
+            },
+            _ =>
+                vec![packet_with(packet.pid, "")],
+        };
+
+        if let Some(delay) = config.delay() {
+            thread::sleep(delay);
+        }
+
+        let mut buffer = Vec::new();
+        for reply in &replies {
+            reply.serialize_into(&mut buffer);
+        }
+        stream.write_all(&buffer)?;
+    }
+}
+
+fn packet_with<B: Into<Vec<u8>>>(id: i32, body: B) -> RawPacket {
+    RawPacket::new_response_value(id, body)
+        .expect("body fits into a packet")
+}
+
+/// A started [`MockServer`](struct.MockServer.html). Dropping it stops accepting
+/// connections; open connections are served until the client closes them.
+pub struct RunningMock {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    commands: Arc<Mutex<Vec<String>>>,
+}
+
+impl RunningMock {
+    /// The address the mock server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// All commands received so far, over all connections, including safe commands.
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl Drop for RunningMock {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wakes up the accepting thread, so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
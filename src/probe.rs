@@ -0,0 +1,187 @@
+//! # Probe
+//!
+//! Which [termination strategy](../termination/index.html) works depends on the server, and
+//! so does the shape of its auth reply. Instead of guessing, a client can probe the server
+//! right after authentication: [`RconClient::probe`](../client/struct.RconClient.html#method.probe)
+//! sends the probe packet of every candidate strategy, one after the other, and the first one
+//! the server answers in time is used from then on.
+//!
+//! The candidates, in this order:
+//!
+//! - the `SERVERDATA_RESPONSE_VALUE` trick ([`ResponseValueMirror`](../termination/struct.ResponseValueMirror.html)),
+//! - a packet of an unknown type ([`InvalidTypeProbe`](../termination/struct.InvalidTypeProbe.html)),
+//! - the safe command, if one is set with [`Probe::safe_command`](struct.Probe.html#method.safe_command).
+//!
+//! If the server answers none of them, responses are ended by a
+//! [`QuietPeriod`](../termination/struct.QuietPeriod.html).
+//!
+//! The outcome is a [`ServerProfile`](struct.ServerProfile.html). It can be cached, with the
+//! `serde` feature enabled also serialized, and handed to
+//! [`RconClient::open_with_profile`](../client/struct.RconClient.html#method.open_with_profile)
+//! on the next connection to skip the probing.
+//!
+//! A client opened with a profile ends responses as the profile says, and expects the auth
+//! reply of the profile: a server which answered with a single `SERVERDATA_AUTH_RESPONSE`
+//! when probed, but sends another packet first, violates the protocol.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{client::*, error::*, probe::*, server::*};
+//! use std::thread;
+//!
+//! let server =
+//!     RconServer::bind("127.0.0.1:0", "mypass", |_: &CommandRequest| "players: 3".to_string())
+//!     .unwrap();
+//! let addr = server.local_addr().unwrap();
+//! thread::spawn(move || server.serve());
+//!
+//! let mut rcon = RconClient::open(addr.to_string(), "mypass", None::<String>, None).unwrap();
+//! let profile = rcon.probe(&Probe::new()).unwrap();
+//! assert_eq!(profile, ServerProfile { auth_reply: AuthReply::Double,
+//!                                     termination: Termination::ResponseValueMirror });
+//! assert_eq!(rcon.exec("status").unwrap(), "players: 3");
+//!
+//! // the next connection reuses the profile.
+//! let mut rcon = RconClient::open_with_profile(addr.to_string(), "mypass", &profile, None).unwrap();
+//! assert_eq!(rcon.exec("status").unwrap(), "players: 3");
+//!
+//! // a profile of a server with a single auth reply does not fit this one.
+//! let single = ServerProfile { auth_reply: AuthReply::Single, ..profile };
+//! assert!(matches!(RconClient::open_with_profile(addr.to_string(), "mypass", &single, None),
+//!                  Err(RconError::Protocol(ProtocolError::NoAuthResponse))));
+//! ```
+
+use super::raw_packet::*;
+use super::termination::*;
+
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The shape of the auth reply of a server.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AuthReply {
+    /// Only a `SERVERDATA_AUTH_RESPONSE`.
+    Single,
+    /// An empty `SERVERDATA_RESPONSE_VALUE`, followed by the `SERVERDATA_AUTH_RESPONSE`, as
+    /// the spec describes it.
+    Double,
+}
+
+/// A built-in termination strategy, as a value which can be stored.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Termination {
+    /// See [`ResponseValueMirror`](../termination/struct.ResponseValueMirror.html).
+    ResponseValueMirror,
+    /// See [`InvalidTypeProbe`](../termination/struct.InvalidTypeProbe.html); the type of
+    /// the probe packet.
+    InvalidTypeProbe(i32),
+    /// See [`SafeCommand`](../termination/struct.SafeCommand.html); the command.
+    SafeCommand(String),
+    /// See [`QuietPeriod`](../termination/struct.QuietPeriod.html); the period.
+    QuietPeriod(Duration),
+    /// See [`SinglePacket`](../termination/struct.SinglePacket.html).
+    SinglePacket,
+}
+
+impl Termination {
+    /// Creates the strategy. Fails for a safe command which does not fit into a packet.
+    pub fn strategy(&self) -> Result<Box<dyn TerminationStrategy>, RawPacketCreationError> {
+        Ok(match self {
+            Termination::ResponseValueMirror => Box::new(ResponseValueMirror),
+            Termination::InvalidTypeProbe(ptype) => Box::new(InvalidTypeProbe::new(*ptype)),
+            Termination::SafeCommand(command) => Box::new(SafeCommand::new(command.as_str())?),
+            Termination::QuietPeriod(period) => Box::new(QuietPeriod::new(*period)),
+            Termination::SinglePacket => Box::new(SinglePacket),
+        })
+    }
+
+    // The packet testing whether the server supports this strategy; it needs to be
+    // answered with a packet of the id `id`. A quiet period works with every server and
+    // has none; whether a server splits responses cannot be probed.
+    pub(crate) fn probe_packet(&self, id: i32) -> Result<Option<RawPacket>, RawPacketCreationError> {
+        match self {
+            Termination::ResponseValueMirror => RawPacket::new_response_value(id, "").map(Some),
+            Termination::InvalidTypeProbe(ptype) => RawPacket::new(id, *ptype, "").map(Some),
+            Termination::SafeCommand(command) => RawPacket::new_exec(id, command.as_str()).map(Some),
+            Termination::QuietPeriod(_) | Termination::SinglePacket => Ok(None),
+        }
+    }
+}
+
+/// What probing found out about a server. See the [module documentation](index.html).
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ServerProfile {
+    /// How the server answered the auth request.
+    pub auth_reply: AuthReply,
+    /// How the end of responses is found.
+    pub termination: Termination,
+}
+
+impl ServerProfile {
+    /// Creates the termination strategy of the profile, see
+    /// [`Termination::strategy`](enum.Termination.html#method.strategy).
+    pub fn strategy(&self) -> Result<Box<dyn TerminationStrategy>, RawPacketCreationError> {
+        self.termination.strategy()
+    }
+}
+
+/// The configuration of the probing. See the [module documentation](index.html).
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub(crate) timeout: Duration,
+    pub(crate) safe_command: Option<String>,
+    pub(crate) quiet_period: Duration,
+}
+
+impl Probe {
+    /// Waits a second for the answer to every probe packet, has no safe command and falls
+    /// back to a quiet period of 250 milliseconds.
+    pub fn new() -> Probe {
+        Probe::default()
+    }
+
+    /// Waits `timeout` for the answer to every probe packet. Slow servers need more.
+    pub fn timeout(mut self, timeout: Duration) -> Probe {
+        self.timeout = timeout.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Also tries `command` as safe command, after the other candidates.
+    pub fn safe_command<C: Into<String>>(mut self, command: C) -> Probe {
+        self.safe_command = Some(command.into());
+        self
+    }
+
+    /// Ends responses after `period` of silence if the server answers no probe packet.
+    pub fn quiet_period(mut self, period: Duration) -> Probe {
+        self.quiet_period = period;
+        self
+    }
+
+    // The candidates in the order they are tried.
+    pub(crate) fn candidates(&self) -> Vec<Termination> {
+        let mut candidates =
+            vec![Termination::ResponseValueMirror,
+                 Termination::InvalidTypeProbe(InvalidTypeProbe::DEFAULT_TYPE)];
+        if let Some(command) = &self.safe_command {
+            candidates.push(Termination::SafeCommand(command.clone()));
+        }
+        candidates
+    }
+}
+
+impl Default for Probe {
+    fn default() -> Probe {
+        Probe {
+            timeout: Duration::from_secs(1),
+            safe_command: None,
+            quiet_period: Duration::from_millis(250),
+        }
+    }
+}
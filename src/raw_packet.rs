@@ -1,15 +1,22 @@
-use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt};
 use std::io::{Write, Read, Error, ErrorKind};
 use std::fmt;
+use std::borrow::Cow;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+
+#[cfg(feature = "futures")]
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 /// Gives the underlying structure of a packet of any type.
 /// There are serialization and deserialization functions
 /// defined. No application level consistency is checked, besides
 /// that the body cannot be larger then 4086 (which is 4096-10).
 ///
-/// Within the spec, only ASCII is allowed where in this implementation
-/// the body is of type `String` so generally UTF-8 is possible which the
-/// server-side might not support when sticking to the protocol.
+/// Within the spec, only ASCII is allowed. In practice servers send about
+/// anything, e.g. Source servers send player names in Latin-1. Hence the body
+/// is kept as raw bytes and can be decoded into text with the `body_*` methods
+/// or a [`TextDecoding`](enum.TextDecoding.html).
 ///
 /// Entities of this type are introduced through the `new` functions; they
 /// calculate a suitable `psize` according to the spec. The field `psize` is
@@ -25,15 +32,24 @@ use std::fmt;
 /// let auth_request_packet =
 ///    RawPacket::new_auth(0, "mypass").unwrap();
 ///
-/// assert_eq!(auth_request_packet.pbody, "mypass");
+/// assert_eq!(auth_request_packet.pbody, b"mypass");
+/// assert_eq!(auth_request_packet.body_utf8(), Ok("mypass"));
 /// assert_eq!(auth_request_packet.ptype, PacketType::RequestAuth.as_i32());
+///
+/// // a body with a Latin-1 encoded name, which is no valid UTF-8.
+/// let response =
+///    RawPacket::new_response_value(0, &b"Jos\xe9"[..]).unwrap();
+///
+/// assert!(response.body_utf8().is_err());
+/// assert_eq!(response.body_utf8_lossy(), "Jos\u{fffd}");
+/// assert_eq!(response.body_latin1(), "Jos\u{e9}");
 /// ```
 #[derive(Debug, Eq, PartialEq)]
 pub struct RawPacket {
     psize: i32,
     pub pid: i32,
     pub ptype: i32,
-    pub pbody: String,
+    pub pbody: Vec<u8>,
 }
 
 /// When creating a `RawPacket` consistency checks may apply
@@ -77,22 +93,123 @@ impl RawPacketCreationError {
     }
 }
 
+/// The smallest valid `psize`: id, type and the two terminating nulls of an empty body.
+pub const MIN_PACKET_SIZE: i32 = 10;
+
+/// The largest `psize` the protocol allows.
+pub const MAX_PACKET_SIZE: i32 = 4096;
+
+/// The largest body a packet can have.
+pub const MAX_BODY_SIZE: i32 = MAX_PACKET_SIZE - MIN_PACKET_SIZE;
+
+/// The body of the packet a server sends right after mirroring an empty
+/// `SERVERDATA_RESPONSE_VALUE`, as described in the
+/// [spec](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#Multiple-packet_Responses).
+pub const RESPONSE_VALUE_MARKER: &[u8] = &[0x00, 0x01, 0x00, 0x00];
+
+/// Received data which cannot be decoded into a `RawPacket` is reported as a
+/// `PacketDecodeError`.
+///
+/// Where decoding happens on top of `std::io` (as in [`RawPacket::deserialize`](struct.RawPacket.html#method.deserialize)),
+/// it is wrapped into an `std::io::Error` of kind `InvalidData`, which can be retrieved
+/// again by `get_ref` and `downcast_ref`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PacketDecodeError {
+    /// The `psize` is smaller than `MIN_PACKET_SIZE`.
+    SizeTooSmall(i32),
+    /// The `psize` is larger than the allowed maximum.
+    SizeTooLarge(i32),
+    /// The body is not followed by the two terminating nulls.
+    MissingTerminator,
+    /// The data does not have the length the `psize` says, e.g. the stream ended within
+    /// the packet.
+    SizeMismatch,
+}
+
+impl fmt::Display for PacketDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PacketDecodeError::SizeTooSmall(psize) =>
+                write!(f, "Packet size {} is smaller than {}.", psize, MIN_PACKET_SIZE),
+            PacketDecodeError::SizeTooLarge(psize) =>
+                write!(f, "Packet size {} exceeds the maximum.", psize),
+            PacketDecodeError::MissingTerminator =>
+                write!(f, "Packet is not terminated by two nulls."),
+            PacketDecodeError::SizeMismatch =>
+                write!(f, "Packet length does not match the packet size."),
+        }
+    }
+}
+
+impl std::error::Error for PacketDecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+impl From<PacketDecodeError> for Error {
+    fn from(e: PacketDecodeError) -> Error {
+        Error::new(ErrorKind::InvalidData, e)
+    }
+}
+
+/// Checks the `psize` read from the wire and returns the number of bytes
+/// which follow it, i.e. the rest of the frame.
+pub(crate) fn frame_size(psize: i32) -> Result<usize, PacketDecodeError> {
+    frame_size_within(psize, MAX_PACKET_SIZE as usize)
+}
+
+/// Like `frame_size`, but with `max_packet_size` as the largest allowed `psize`.
+pub(crate) fn frame_size_within(psize: i32, max_packet_size: usize) -> Result<usize, PacketDecodeError> {
+    if psize < MIN_PACKET_SIZE {
+        return Err(PacketDecodeError::SizeTooSmall(psize));
+    }
+    if psize as usize > max_packet_size {
+        return Err(PacketDecodeError::SizeTooLarge(psize));
+    }
+    Ok(psize as usize)
+}
+
+/// Decodes one complete frame, i.e. a buffer holding exactly the bytes the `psize`
+/// announces.
+pub(crate) fn decode_frame(frame: &[u8]) -> Result<RawPacket, PacketDecodeError> {
+    match RawPacketRef::parse(frame)? {
+        Some((packet, length)) if length == frame.len() => Ok(packet.to_packet()),
+        _ => Err(PacketDecodeError::SizeMismatch),
+    }
+}
+
+// Reading the rest of a frame fails with `UnexpectedEof` if the stream ends before the
+// `psize` is reached.
+fn cut_short(e: Error) -> Error {
+    match e.kind() {
+        ErrorKind::UnexpectedEof => PacketDecodeError::SizeMismatch.into(),
+        _ => e,
+    }
+}
+
+fn read_i32(bytes: &[u8]) -> i32 {
+    let mut buffer = [0u8; 4];
+    buffer.copy_from_slice(bytes);
+    i32::from_le_bytes(buffer)
+}
+
 impl RawPacket {
     /// Creates a new raw packet, which is consistent with the spec, expect
     /// for the type, which is allowed to be any number. In other words: this
     /// creates a `RawPacket` with a suitable `psize`.
-    pub fn new<T: Into<String>>(id: i32,
-                                raw_type: i32,
-                                body: T) -> Result<RawPacket, RawPacketCreationError> {
-        // get as a genuine string, what ever it was:
-        let body_str : String = body.into();
+    pub fn new<T: Into<Vec<u8>>>(id: i32,
+                                 raw_type: i32,
+                                 body: T) -> Result<RawPacket, RawPacketCreationError> {
+        // get as genuine bytes, what ever it was:
+        let body_bytes : Vec<u8> = body.into();
 
         //*******************
         // CONSISTENCY CHECKS
         //*******************
         // check if the packet size is sufficient:
-        let len = body_str.len() as i32;
-        if len > 4086 {
+        let len = body_bytes.len() as i32;
+        if len > MAX_BODY_SIZE {
             return Err(RawPacketCreationError::BodyTooLarge);
         }
         // CONSISTENCY CHECKS: END
@@ -106,7 +223,7 @@ impl RawPacket {
                 psize,
                 pid: id,
                 ptype: raw_type,
-                pbody: body_str,
+                pbody: body_bytes,
             })
     }
 
@@ -118,55 +235,127 @@ impl RawPacket {
     /// - Write the body as null-terminated string.
     /// - Add another null to end the packet.
     ///
-    /// Since `String` isn't null-terminated, this null is added manually
+    /// Since the body isn't null-terminated, this null is added manually
     /// after writing the body.
+    ///
+    /// The packet is encoded into a buffer first, which is written with a single `write_all`.
     pub fn serialize<T: Write>(&self, w: &mut T) -> std::io::Result<()> {
-        w.write_i32::<LittleEndian>(self.psize)?;
-        w.write_i32::<LittleEndian>(self.pid)?;
-        w.write_i32::<LittleEndian>(self.ptype)?;
-
-        // body needs to be null-terminated string.
-        // Strings in rust aren't null-terminated.
-        w.write(self.pbody.as_bytes())?; // write bytes
-        w.write_u8(0)?; // write the null for this string
-
-        // protocol wants another null afterwards.
-        w.write_u8(0)?;
+        // encode the whole packet first, so it is handed to the writer in one
+        // piece; on a raw `TcpStream` this means one segment instead of five.
+        let mut buffer = Vec::new();
+        self.serialize_into(&mut buffer);
 
+        w.write_all(&buffer)?;
         w.flush()?;
 
         Ok(())
-        
+    }
+
+    /// Appends the serialized packet (see [`serialize`](struct.RawPacket.html#method.serialize))
+    /// to `buffer`, reserving the needed space once instead of writing field by field.
+    pub fn serialize_into(&self, buffer: &mut Vec<u8>) {
+        RawPacketRef::from(self).serialize_into(buffer)
     }
 
     /// Deserialization according to the spec. See [`serialize`](struct.RawPacket.html#method.serialize).
+    ///
+    /// The received data is checked strictly: the `psize` needs to be within `MIN_PACKET_SIZE`
+    /// and `MAX_PACKET_SIZE` and the body needs to be followed by the two terminating nulls.
+    /// Nulls within the body are accepted, since servers use them e.g. for the
+    /// [`RESPONSE_VALUE_MARKER`](constant.RESPONSE_VALUE_MARKER.html). Violations are reported as `PacketDecodeError` wrapped into
+    /// an `std::io::Error` of kind `InvalidData`; so is a stream which ends within a packet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{decoder::*, raw_packet::*};
+    ///
+    /// let decode_error = |wire: &[u8]| {
+    ///     let error = RawPacket::deserialize(&mut &wire[..]).unwrap_err();
+    ///     error.get_ref().unwrap().downcast_ref::<PacketDecodeError>().copied()
+    /// };
+    /// let with_psize = |psize: i32, rest: &[u8]| [&psize.to_le_bytes()[..], rest].concat();
+    ///
+    /// // a packet with a `psize` of 4 (too small) and a negative one.
+    /// for psize in &[4i32, -1] {
+    ///     assert_eq!(decode_error(&with_psize(*psize, &[0; 10])),
+    ///                Some(PacketDecodeError::SizeTooSmall(*psize)));
+    /// }
+    ///
+    /// // a `psize` above `MAX_PACKET_SIZE`.
+    /// let oversized = with_psize(5000, &[0; 5000]);
+    /// assert_eq!(decode_error(&oversized), Some(PacketDecodeError::SizeTooLarge(5000)));
+    /// assert_eq!(RawPacketRef::parse(&oversized), Err(PacketDecodeError::SizeTooLarge(5000)));
+    ///
+    /// // a body of "hi" without the two terminating nulls.
+    /// let unterminated = with_psize(12, &[1, 0, 0, 0, 0, 0, 0, 0, b'h', b'i', b'!', b'!']);
+    /// assert_eq!(decode_error(&unterminated), Some(PacketDecodeError::MissingTerminator));
+    /// let mut decoder = PacketDecoder::new();
+    /// decoder.feed(&unterminated);
+    /// assert_eq!(decoder.next_packet(), Err(PacketDecodeError::MissingTerminator));
+    ///
+    /// // a stream which ends before the 20 bytes the `psize` announces.
+    /// let cut_short = with_psize(20, &[0; 10]);
+    /// assert_eq!(decode_error(&cut_short), Some(PacketDecodeError::SizeMismatch));
+    /// ```
     pub fn deserialize<T: Read>(r: &mut T) -> std::io::Result<RawPacket> {
         let psize = r.read_i32::<LittleEndian>()?;
-        let pid = r.read_i32::<LittleEndian>()?;
-        let ptype = r.read_i32::<LittleEndian>()?;
 
-        // body size is the packet size
-        // - 4 (id field)
-        // - 4 (type field)
-        // - 1 (terminating null of string)
-        // - 1 (terminating null for packet)
-        // = -10
-        let body_length : usize = (psize as usize) - 10;
-        let mut body_buffer = Vec::with_capacity(body_length);
+        // the frame is the packet size plus the size field itself.
+        let mut frame = psize.to_le_bytes().to_vec();
+        frame.resize(4 + frame_size(psize)?, 0);
+        r.read_exact(&mut frame[4..]).map_err(cut_short)?;
 
-        r.take(body_length as u64).read_to_end(&mut body_buffer)?;
-        let pbody = String::from_utf8(body_buffer)
-            .map_err(|e| Error::new(ErrorKind::Other,
-                                    format!("Cannot from_utf8 on body_buffer: {}", e)))?;
+        Ok(decode_frame(&frame)?)
+    }
 
-        r.read_u8()?; // string null
-        r.read_u8()?; // packet null
+    /// Async version of [`serialize`](struct.RawPacket.html#method.serialize) for any
+    /// `futures::io::AsyncWrite`. The packet is serialized into a buffer first and then
+    /// written as a whole.
+    ///
+    /// Only available with the `futures` feature enabled.
+    #[cfg(feature = "futures")]
+    pub async fn serialize_async<T: AsyncWrite + Unpin>(&self, w: &mut T) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        self.serialize_into(&mut buffer);
+
+        w.write_all(&buffer).await?;
+        w.flush().await
+    }
 
-        let packet = 
-            RawPacket::new(pid, ptype, pbody)
-            .map_err(|e| e.to_io_error())?;
+    /// Async version of [`deserialize`](struct.RawPacket.html#method.deserialize) for any
+    /// `futures::io::AsyncRead`. Reads the whole frame as announced by `psize`, then
+    /// deserializes it.
+    ///
+    /// Only available with the `futures` feature enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::raw_packet::*;
+    /// use futures::executor::block_on;
+    /// use futures::io::Cursor;
+    ///
+    /// let packet = RawPacket::new_exec(7, "status").unwrap();
+    ///
+    /// let mut wire = Cursor::new(Vec::new());
+    /// block_on(packet.serialize_async(&mut wire)).unwrap();
+    ///
+    /// wire.set_position(0);
+    /// let received = block_on(RawPacket::deserialize_async(&mut wire)).unwrap();
+    /// assert_eq!(received, packet);
+    /// ```
+    #[cfg(feature = "futures")]
+    pub async fn deserialize_async<T: AsyncRead + Unpin>(r: &mut T) -> std::io::Result<RawPacket> {
+        let mut size_buffer = [0u8; 4];
+        r.read_exact(&mut size_buffer).await?;
+        let psize = i32::from_le_bytes(size_buffer);
+
+        let mut frame = size_buffer.to_vec();
+        frame.resize(4 + frame_size(psize)?, 0);
+        r.read_exact(&mut frame[4..]).await.map_err(cut_short)?;
 
-        Ok(packet)
+        Ok(decode_frame(&frame)?)
     }
 
     /// Provides the base line for all convenience functions to create packets of a specific type
@@ -191,28 +380,44 @@ impl RawPacket {
     /// assert_eq!(exec_packet_ftype, exec_packet_conv);
     /// // and hence by transitivity ... 
     /// ```
-    pub fn new_from_type<T: Into<String>>(id: i32,
+    pub fn new_from_type<T: Into<Vec<u8>>>(id: i32,
                                           raw_body: T,
                                           ptype: &PacketType) -> Result<RawPacket, RawPacketCreationError> {
         Self::new(id, ptype.as_i32(), raw_body)
     }
 
-    pub fn new_auth<T: Into<String>>(id: i32, pass: T) -> Result<RawPacket, RawPacketCreationError> {
+    pub fn new_auth<T: Into<Vec<u8>>>(id: i32, pass: T) -> Result<RawPacket, RawPacketCreationError> {
         Self::new_from_type(id, pass, &PacketType::RequestAuth)
     }
 
-    pub fn new_exec<T: Into<String>>(id: i32, command: T) -> Result<RawPacket, RawPacketCreationError> {
+    pub fn new_exec<T: Into<Vec<u8>>>(id: i32, command: T) -> Result<RawPacket, RawPacketCreationError> {
         Self::new_from_type(id, command, &PacketType::RequestExecCommand)
     }
 
-    pub fn new_response_auth<T: Into<String>>(id: i32, value: T) -> Result<RawPacket, RawPacketCreationError> {
+    pub fn new_response_auth<T: Into<Vec<u8>>>(id: i32, value: T) -> Result<RawPacket, RawPacketCreationError> {
         Self::new_from_type(id, value, &PacketType::ResponseAuth)
     }
 
-    pub fn new_response_value<T: Into<String>>(id: i32, value: T) -> Result<RawPacket, RawPacketCreationError> {
+    pub fn new_response_value<T: Into<Vec<u8>>>(id: i32, value: T) -> Result<RawPacket, RawPacketCreationError> {
         Self::new_from_type(id, value, &PacketType::ResponseValue)
     }
 
+    /// The body as UTF-8 text. Fails if the body is no valid UTF-8.
+    pub fn body_utf8(&self) -> Result<&str, Utf8Error> {
+        std::str::from_utf8(&self.pbody)
+    }
+
+    /// The body as UTF-8 text, where invalid sequences are replaced by `U+FFFD`.
+    pub fn body_utf8_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.pbody)
+    }
+
+    /// The body as Latin-1 (ISO-8859-1) text. This never fails, since every byte
+    /// is a Latin-1 character.
+    pub fn body_latin1(&self) -> String {
+        latin1_to_string(&self.pbody)
+    }
+
     /// Retrieves the `ptype` as a `PacketType`, where the packet is
     /// seen as a response. (See [`PacketType`](enum.PacketType.html) for more
     /// information.)
@@ -228,6 +433,161 @@ impl RawPacket {
 }
 
 
+/// A borrowed view on a packet, where the body points into the buffer the packet was
+/// parsed from. Parsing does not allocate, which suits e.g. proxies and capture tools
+/// looking at lots of packets. [`to_packet`](#method.to_packet) gives an owned `RawPacket`.
+///
+/// # Example
+///
+/// ```
+/// use facio::raw_packet::*;
+///
+/// let mut wire = Vec::new();
+/// RawPacket::new_response_value(5, "hello").unwrap().serialize_into(&mut wire);
+/// RawPacket::new_response_value(6, "world").unwrap().serialize_into(&mut wire);
+///
+/// // parse the first packet; the second one stays in the buffer.
+/// let (packet, length) = RawPacketRef::parse(&wire).unwrap().unwrap();
+/// assert_eq!(packet.pid, 5);
+/// assert_eq!(packet.pbody, b"hello");
+///
+/// let (next, _) = RawPacketRef::parse(&wire[length..]).unwrap().unwrap();
+/// assert_eq!(next.to_packet(), RawPacket::new_response_value(6, "world").unwrap());
+///
+/// // an incomplete packet is no error, there is just nothing to parse yet.
+/// assert_eq!(RawPacketRef::parse(&wire[..length - 1]), Ok(None));
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RawPacketRef<'a> {
+    pub pid: i32,
+    pub ptype: i32,
+    pub pbody: &'a [u8],
+}
+
+impl<'a> RawPacketRef<'a> {
+    /// Parses the packet at the start of `input`, with the same checks as
+    /// [`RawPacket::deserialize`](struct.RawPacket.html#method.deserialize). Returns the packet
+    /// and the number of bytes it takes up in `input`, or `None` if `input` does not hold
+    /// a complete packet yet.
+    pub fn parse(input: &'a [u8]) -> Result<Option<(RawPacketRef<'a>, usize)>, PacketDecodeError> {
+        RawPacketRef::parse_with_max_size(input, MAX_PACKET_SIZE as usize)
+    }
+
+    /// Like [`parse`](#method.parse), but accepts packets with a `psize` up to
+    /// `max_packet_size` instead of `MAX_PACKET_SIZE`. Some servers, e.g. Factorio's, send
+    /// long responses as one packet exceeding the protocol maximum.
+    pub fn parse_with_max_size(input: &'a [u8],
+                               max_packet_size: usize) -> Result<Option<(RawPacketRef<'a>, usize)>, PacketDecodeError> {
+        if input.len() < 4 {
+            return Ok(None);
+        }
+
+        // the frame is the packet size plus the size field itself.
+        let frame_length = 4 + frame_size_within(read_i32(&input[..4]), max_packet_size)?;
+        if input.len() < frame_length {
+            return Ok(None);
+        }
+
+        let pid = read_i32(&input[4..8]);
+        let ptype = read_i32(&input[8..12]);
+
+        // the body is followed by the null terminating the string and the null
+        // terminating the packet.
+        let (body, terminators) = input[12..frame_length].split_at(frame_length - 14);
+        if terminators != [0, 0] {
+            return Err(PacketDecodeError::MissingTerminator);
+        }
+
+        Ok(Some((RawPacketRef { pid, ptype, pbody: body }, frame_length)))
+    }
+
+    /// Copies the packet into an owned `RawPacket`.
+    pub fn to_packet(&self) -> RawPacket {
+        RawPacket {
+            psize: self.pbody.len() as i32 + MIN_PACKET_SIZE,
+            pid: self.pid,
+            ptype: self.ptype,
+            pbody: self.pbody.to_vec(),
+        }
+    }
+
+    /// Appends the serialized packet to `buffer`. See
+    /// [`RawPacket::serialize_into`](struct.RawPacket.html#method.serialize_into).
+    pub fn serialize_into(&self, buffer: &mut Vec<u8>) {
+        let psize = self.pbody.len() as i32 + MIN_PACKET_SIZE;
+
+        buffer.reserve(4 + psize as usize);
+        buffer.extend_from_slice(&psize.to_le_bytes());
+        buffer.extend_from_slice(&self.pid.to_le_bytes());
+        buffer.extend_from_slice(&self.ptype.to_le_bytes());
+        buffer.extend_from_slice(self.pbody);
+        // the null for the body string and the null for the packet.
+        buffer.extend_from_slice(&[0, 0]);
+    }
+}
+
+impl<'a> From<&'a RawPacket> for RawPacketRef<'a> {
+    fn from(packet: &'a RawPacket) -> RawPacketRef<'a> {
+        RawPacketRef {
+            pid: packet.pid,
+            ptype: packet.ptype,
+            pbody: &packet.pbody,
+        }
+    }
+}
+
+impl From<RawPacketRef<'_>> for RawPacket {
+    fn from(packet: RawPacketRef<'_>) -> RawPacket {
+        packet.to_packet()
+    }
+}
+
+fn latin1_to_string(bytes: &[u8]) -> String {
+    // Latin-1 is exactly the first 256 code points of unicode.
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Selects how packet bodies are decoded into text, e.g. by the clients.
+///
+/// # Example
+///
+/// ```
+/// use facio::raw_packet::*;
+///
+/// let body = b"Jos\xe9".to_vec();
+///
+/// assert!(TextDecoding::Utf8.decode(body.clone()).is_err());
+/// assert_eq!(TextDecoding::Utf8Lossy.decode(body.clone()).unwrap(), "Jos\u{fffd}");
+/// assert_eq!(TextDecoding::Latin1.decode(body).unwrap(), "Jos\u{e9}");
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum TextDecoding {
+    /// Strict UTF-8; bodies with invalid UTF-8 fail to decode.
+    #[default]
+    Utf8,
+    /// UTF-8, where invalid sequences are replaced by `U+FFFD`.
+    Utf8Lossy,
+    /// Latin-1 (ISO-8859-1), as used by e.g. Source servers for player names.
+    Latin1,
+}
+
+impl TextDecoding {
+    /// Decodes `bytes` into text. Only `TextDecoding::Utf8` can fail; the error gives
+    /// access to the original bytes.
+    pub fn decode(&self, bytes: Vec<u8>) -> Result<String, FromUtf8Error> {
+        match self {
+            TextDecoding::Utf8 =>
+                String::from_utf8(bytes),
+            TextDecoding::Utf8Lossy =>
+                Ok(
+                    String::from_utf8(bytes)
+                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())),
+            TextDecoding::Latin1 =>
+                Ok(latin1_to_string(&bytes)),
+        }
+    }
+}
+
 /// Defines the four basic types as stated in the protocol.
 ///
 /// The protocol defines the types as names for certain values
@@ -0,0 +1,158 @@
+//! # Reconnect
+//!
+//! Game servers restart, e.g. nightly, and a long-lived client loses its connection then.
+//! With a [`ReconnectPolicy`](struct.ReconnectPolicy.html) set, see
+//! [`RconClient::set_reconnect_policy`](../client/struct.RconClient.html#method.set_reconnect_policy),
+//! the client does not fail forever afterwards: the next `exec` opens a new connection and
+//! authenticates again, trying a few times with exponentially growing, jittered pauses
+//! between the attempts. If all of them fail, the command fails with
+//! `RconError::Disconnected`, holding the error of the last attempt, and the next command
+//! tries again.
+//!
+//! The command which hit the lost connection failed; whether the server executed it before,
+//! nobody knows. It is therefore only sent again on the new connection if the policy
+//! considers it safe to retry, see [`retry_if`](struct.ReconnectPolicy.html#method.retry_if).
+//! By default, no command is retried.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{client::*, reconnect::*, server::*};
+//! use std::net::Shutdown;
+//! use std::thread;
+//! use std::time::Duration;
+//!
+//! let server =
+//!     RconServer::bind("127.0.0.1:0", "mypass", |_: &CommandRequest| "players: 3".to_string())
+//!     .unwrap();
+//! let addr = server.local_addr().unwrap();
+//! thread::spawn(move || server.serve());
+//!
+//! let mut rcon = RconClient::open(addr.to_string(), "mypass", None::<String>, None).unwrap();
+//! rcon.set_reconnect_policy(
+//!     ReconnectPolicy::new()
+//!         .max_attempts(3)
+//!         .backoff(Duration::from_millis(10), Duration::from_millis(100))
+//!         .retry_if(|command| command == "status"));
+//!
+//! // the connection gets lost, e.g. since the server restarted.
+//! rcon.get_ref().shutdown(Shutdown::Both).unwrap();
+//!
+//! // a read-only command is retried on a new connection.
+//! assert_eq!(rcon.exec("status").unwrap(), "players: 3");
+//! assert_eq!(rcon.reconnects(), 1);
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Decides whether a command is sent again after a reconnect.
+type RetryIf = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// When and how often a client reconnects. See the [module documentation](index.html).
+#[derive(Clone)]
+pub struct ReconnectPolicy {
+    pub(crate) max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+    pub(crate) connect_timeout: Option<Duration>,
+    retry: Option<RetryIf>,
+}
+
+impl ReconnectPolicy {
+    /// Tries 5 times to reconnect, with pauses starting at 100 milliseconds, doubling up to
+    /// 10 seconds, jittered by up to half of them. Connects wait up to 5 seconds. No command
+    /// is retried.
+    pub fn new() -> ReconnectPolicy {
+        ReconnectPolicy::default()
+    }
+
+    /// Gives up after `attempts` failed attempts to reconnect; at least one.
+    pub fn max_attempts(mut self, attempts: u32) -> ReconnectPolicy {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Pauses `initial` before the second attempt, doubling the pause for every further one,
+    /// up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> ReconnectPolicy {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Shortens every pause by a random part of up to `fraction` of it, between `0.0` (no
+    /// jitter) and `1.0`, so clients losing their connection at once do not reconnect at
+    /// once.
+    pub fn jitter(mut self, fraction: f64) -> ReconnectPolicy {
+        self.jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Lets a connect fail after `timeout`; `None` means it waits as long as the system does.
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> ReconnectPolicy {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sends a command, which failed since the connection got lost, again on the new
+    /// connection if `retry` returns `true` for it, e.g. for read-only commands.
+    pub fn retry_if<F>(mut self, retry: F) -> ReconnectPolicy
+        where F: Fn(&str) -> bool + Send + Sync + 'static {
+        self.retry = Some(Arc::new(retry));
+        self
+    }
+
+    /// The pause before the attempt `attempt`, counted from 0: none before the first one.
+    pub fn pause(&self, attempt: u32) -> Duration {
+        if attempt == 0 {
+            return Duration::ZERO;
+        }
+
+        let factor = 2u32.saturating_pow(attempt - 1);
+        let pause = self.initial_backoff.saturating_mul(factor).min(self.max_backoff);
+        pause.mul_f64(1.0 - self.jitter * random_fraction())
+    }
+
+    /// Whether `command` is sent again after a reconnect.
+    pub fn should_retry(&self, command: &str) -> bool {
+        self.retry.as_ref().is_some_and(|retry| retry(command))
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            jitter: 0.5,
+            connect_timeout: Some(Duration::from_secs(5)),
+            retry: None,
+        }
+    }
+}
+
+impl fmt::Debug for ReconnectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReconnectPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("retry", &self.retry.is_some())
+            .finish()
+    }
+}
+
+// A random number in [0, 1), from the randomly seeded hasher of the standard library.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    // 53 random bits give a uniform float in [0, 1).
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
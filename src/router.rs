@@ -0,0 +1,415 @@
+//! # Router
+//!
+//! A [`CommandHandler`](../server/trait.CommandHandler.html) which dispatches commands by name.
+//! Commands are declared with their arguments, which are parsed and checked before the
+//! handler of a command is called. The router answers `help` with a list of all commands
+//! and unknown commands with a hint to `help`.
+//!
+//! Arguments are separated by whitespace; arguments containing whitespace can be quoted with
+//! `"`, where `\"` and `\\` stand for a quote and a backslash.
+//!
+//! Commands can require a role of the [`Identity`](../auth/struct.Identity.html) the client
+//! authenticated as; others are denied.
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{auth::*, router::*, server::*};
+//!
+//! let router = CommandRouter::new()
+//!     .command(Command::new("say")
+//!                  .about("Sends a message to all players")
+//!                  .arg::<String>("message"),
+//!              |_: &CommandRequest, args: &Args| {
+//!                  format!("said: {}", args.get::<String>("message").unwrap())
+//!              })
+//!     .command(Command::new("ban")
+//!                  .about("Bans a player")
+//!                  .role("admin")
+//!                  .arg::<String>("player")
+//!                  .optional_arg::<u32>("minutes"),
+//!              |_: &CommandRequest, args: &Args| {
+//!                  match args.get::<u32>("minutes") {
+//!                      Some(minutes) =>
+//!                          format!("banned {} for {} minutes",
+//!                                  args.get::<String>("player").unwrap(), minutes),
+//!                      None =>
+//!                          format!("banned {}", args.get::<String>("player").unwrap()),
+//!                  }
+//!              });
+//!
+//! // the router is a `CommandHandler`, usually passed to `RconServer::bind`.
+//! let admin = Identity::new("alice", &["admin"]);
+//! let exec_as = |identity: &Identity, command: &str| router.handle(
+//!     &CommandRequest { id: 0, command, peer: "127.0.0.1:1234".parse().unwrap(), identity });
+//! let exec = |command: &str| exec_as(&admin, command);
+//!
+//! assert_eq!(exec("say \"hello there\""), "said: hello there");
+//! assert_eq!(exec("ban troll 60"), "banned troll for 60 minutes");
+//! assert_eq!(exec("ban troll"), "banned troll");
+//! assert_eq!(exec("ban troll soon"),
+//!            "Invalid value 'soon' for <minutes>, expected an unsigned integer. \
+//!             Usage: ban <player> [minutes]");
+//! assert_eq!(exec_as(&Identity::new("bob", &[]), "ban troll"), "Permission denied.");
+//! assert_eq!(exec("kick troll"), "Unknown command 'kick'. Type 'help' for a list of commands.");
+//! assert_eq!(exec("help"),
+//!            "say <message> - Sends a message to all players\n\
+//!             ban <player> [minutes] - Bans a player\n\
+//!             help [command] - Lists the commands");
+//! assert_eq!(exec("help ban"), "ban <player> [minutes] - Bans a player");
+//! assert_eq!(exec("help ban say"), "Too many arguments. Usage: help [command]");
+//! ```
+
+use super::auth::Identity;
+use super::server::{CommandHandler, CommandRequest};
+
+use std::fmt;
+
+/// A type arguments can be parsed into.
+pub trait FromArg: Sized {
+    /// Describes the expected values in error messages, e.g. "an integer".
+    const EXPECTED: &'static str;
+
+    /// Parses `arg`; `None` if it is no valid value.
+    fn from_arg(arg: &str) -> Option<Self>;
+}
+
+impl FromArg for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_arg(arg: &str) -> Option<String> {
+        Some(arg.to_string())
+    }
+}
+
+macro_rules! from_arg_by_parse {
+    ($($t:ty => $expected:expr),*) => {
+        $(
+            impl FromArg for $t {
+                const EXPECTED: &'static str = $expected;
+
+                fn from_arg(arg: &str) -> Option<$t> {
+                    arg.parse().ok()
+                }
+            }
+        )*
+    }
+}
+
+from_arg_by_parse!(i32 => "an integer",
+                   i64 => "an integer",
+                   u32 => "an unsigned integer",
+                   u64 => "an unsigned integer",
+                   f64 => "a number",
+                   bool => "true or false");
+
+struct ArgSpec {
+    name: &'static str,
+    optional: bool,
+    expected: &'static str,
+    check: fn(&str) -> bool,
+}
+
+/// The declaration of a command: its name, a description for `help`, the role required
+/// to execute it and its arguments.
+pub struct Command {
+    name: String,
+    about: String,
+    role: Option<String>,
+    args: Vec<ArgSpec>,
+}
+
+impl Command {
+    /// Declares the command `name`, without any arguments.
+    pub fn new<N: Into<String>>(name: N) -> Command {
+        Command { name: name.into(), about: String::new(), role: None, args: Vec::new() }
+    }
+
+    /// Only allows clients whose identity has the role `role` to execute the command.
+    pub fn role<R: Into<String>>(mut self, role: R) -> Command {
+        self.role = Some(role.into());
+        self
+    }
+
+    /// Sets the description shown by `help`.
+    pub fn about<A: Into<String>>(mut self, about: A) -> Command {
+        self.about = about.into();
+        self
+    }
+
+    /// Adds a required argument of type `T`.
+    ///
+    /// # Panics
+    ///
+    /// If an optional argument was added before, since arguments are assigned in order.
+    pub fn arg<T: FromArg>(mut self, name: &'static str) -> Command {
+        assert!(self.args.iter().all(|arg| !arg.optional),
+                "required argument <{}> after an optional one", name);
+        self.args.push(ArgSpec { name, optional: false, expected: T::EXPECTED, check: check::<T> });
+        self
+    }
+
+    /// Adds an optional argument of type `T`.
+    pub fn optional_arg<T: FromArg>(mut self, name: &'static str) -> Command {
+        self.args.push(ArgSpec { name, optional: true, expected: T::EXPECTED, check: check::<T> });
+        self
+    }
+
+    fn permits(&self, identity: &Identity) -> bool {
+        match &self.role {
+            Some(role) => identity.has_role(role),
+            None => true,
+        }
+    }
+
+    fn usage(&self) -> String {
+        let mut usage = self.name.clone();
+        for arg in &self.args {
+            if arg.optional {
+                usage += &format!(" [{}]", arg.name);
+            } else {
+                usage += &format!(" <{}>", arg.name);
+            }
+        }
+        usage
+    }
+
+    fn parse(&self, tokens: Vec<String>) -> Result<Args, String> {
+        if tokens.len() > self.args.len() {
+            return Err(format!("Too many arguments. Usage: {}", self.usage()));
+        }
+
+        let mut values = Vec::new();
+        let mut tokens = tokens.into_iter();
+        for arg in &self.args {
+            match tokens.next() {
+                Some(token) => {
+                    if !(arg.check)(&token) {
+                        return Err(
+                            format!("Invalid value '{}' for <{}>, expected {}. Usage: {}",
+                                    token, arg.name, arg.expected, self.usage()));
+                    }
+                    values.push((arg.name, token));
+                },
+                None if arg.optional => {},
+                None =>
+                    return Err(
+                        format!("Missing argument <{}>. Usage: {}", arg.name, self.usage())),
+            }
+        }
+        Ok(Args { values })
+    }
+}
+
+fn check<T: FromArg>(arg: &str) -> bool {
+    T::from_arg(arg).is_some()
+}
+
+/// The parsed arguments of a command, as passed to its handler.
+#[derive(Debug)]
+pub struct Args {
+    values: Vec<(&'static str, String)>,
+}
+
+impl Args {
+    /// The argument `name` as `T`. `None` if the argument was not given, which only
+    /// happens for optional arguments.
+    ///
+    /// Since arguments are checked before the handler is called, this does not fail for
+    /// the type the argument was declared with.
+    pub fn get<T: FromArg>(&self, name: &str) -> Option<T> {
+        self.raw(name).and_then(T::from_arg)
+    }
+
+    /// The argument `name` as given by the client.
+    pub fn raw(&self, name: &str) -> Option<&str> {
+        self.values.iter()
+            .find(|(arg, _)| *arg == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A command line cannot be split into arguments.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TokenizeError {
+    /// A quoted argument is not closed.
+    UnterminatedQuote,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenizeError::UnterminatedQuote =>
+                write!(f, "Unterminated quote."),
+        }
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// Splits a command line into its arguments, as the router does. See the
+/// [module documentation](index.html).
+///
+/// # Example
+///
+/// ```
+/// use facio::router::*;
+///
+/// assert_eq!(tokenize(r#"say "a \"quoted\" word" now"#).unwrap(),
+///            vec!["say", r#"a "quoted" word"#, "now"]);
+/// assert_eq!(tokenize(r#"say "open"#), Err(TokenizeError::UnterminatedQuote));
+/// ```
+pub fn tokenize(line: &str) -> Result<Vec<String>, TokenizeError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let first = match chars.next() {
+            Some(c) => c,
+            None => return Ok(tokens),
+        };
+
+        let mut token = String::new();
+        if first == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c) => token.push(c),
+                        None => return Err(TokenizeError::UnterminatedQuote),
+                    },
+                    Some(c) => token.push(c),
+                    None => return Err(TokenizeError::UnterminatedQuote),
+                }
+            }
+        } else {
+            token.push(first);
+            while let Some(c) = chars.peek().copied() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+        }
+        tokens.push(token);
+    }
+}
+
+type Handler = Box<dyn Fn(&CommandRequest, &Args) -> String + Send + Sync>;
+
+/// Dispatches commands to their handlers. See the [module documentation](index.html).
+#[derive(Default)]
+pub struct CommandRouter {
+    commands: Vec<(Command, Handler)>,
+}
+
+impl CommandRouter {
+    /// Creates a router without any commands, besides `help`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{auth::*, router::*, server::*};
+    ///
+    /// // a registered `help` replaces the built-in one.
+    /// let router = CommandRouter::new()
+    ///     .command(Command::new("say").arg::<String>("message"),
+    ///              |_: &CommandRequest, _: &Args| String::new())
+    ///     .command(Command::new("help").about("Shows the manual"),
+    ///              |_: &CommandRequest, _: &Args| "See the manual.".to_string());
+    ///
+    /// let identity = Identity::new("alice", &[]);
+    /// let exec = |command: &str| router.handle(
+    ///     &CommandRequest { id: 0, command, peer: "127.0.0.1:1234".parse().unwrap(), identity: &identity });
+    ///
+    /// assert_eq!(exec("help"), "See the manual.");
+    /// assert_eq!(exec("help say"), "Too many arguments. Usage: help");
+    /// ```
+    pub fn new() -> CommandRouter {
+        CommandRouter::default()
+    }
+
+    /// Registers `command`, handled by `handler`. A command registered under an existing
+    /// name replaces the former one.
+    pub fn command<F>(mut self, command: Command, handler: F) -> CommandRouter
+        where F: Fn(&CommandRequest, &Args) -> String + Send + Sync + 'static {
+        self.commands.retain(|(c, _)| c.name != command.name);
+        self.commands.push((command, Box::new(handler)));
+        self
+    }
+
+    fn help(&self, topic: Option<&str>) -> String {
+        let help = help_command();
+
+        let line = |command: &Command| {
+            if command.about.is_empty() {
+                command.usage()
+            } else {
+                format!("{} - {}", command.usage(), command.about)
+            }
+        };
+
+        match topic {
+            Some(topic) =>
+                match self.commands.iter().find(|(c, _)| c.name == topic) {
+                    Some((command, _)) => line(command),
+                    None if topic == "help" => line(&help),
+                    None => unknown(topic),
+                },
+            None => {
+                // the built-in `help` is only listed while no command of this name is registered.
+                let builtin =
+                    Some(&help).filter(|_| self.commands.iter().all(|(c, _)| c.name != "help"));
+                self.commands.iter()
+                    .map(|(command, _)| command)
+                    .chain(builtin)
+                    .map(line)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
+        }
+    }
+}
+
+// The built-in `help`, unless a command of this name is registered.
+fn help_command() -> Command {
+    Command::new("help").about("Lists the commands").optional_arg::<String>("command")
+}
+
+fn unknown(name: &str) -> String {
+    format!("Unknown command '{}'. Type 'help' for a list of commands.", name)
+}
+
+impl CommandHandler for CommandRouter {
+    fn handle(&self, request: &CommandRequest) -> String {
+        let mut tokens = match tokenize(request.command) {
+            Ok(tokens) => tokens,
+            Err(e) => return e.to_string(),
+        };
+        if tokens.is_empty() {
+            return unknown("");
+        }
+        let name = tokens.remove(0);
+
+        match self.commands.iter().find(|(c, _)| c.name == name) {
+            Some((command, _)) if !command.permits(request.identity) =>
+                "Permission denied.".to_string(),
+            Some((command, handler)) =>
+                match command.parse(tokens) {
+                    Ok(args) => handler(request, &args),
+                    Err(usage) => usage,
+                },
+            None if name == "help" =>
+                match help_command().parse(tokens) {
+                    Ok(args) => self.help(args.raw("command")),
+                    Err(usage) => usage,
+                },
+            None =>
+                unknown(&name),
+        }
+    }
+}
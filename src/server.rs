@@ -0,0 +1,389 @@
+//! # Server
+//!
+//! An embeddable RCON server. [`RconServer`](struct.RconServer.html) listens on a TCP port,
+//! handles the authentication of clients and passes every command it receives to a
+//! [`CommandHandler`](trait.CommandHandler.html), whose answer is sent back to the client.
+//!
+//! Passwords are checked by an [`Authenticator`](../auth/trait.Authenticator.html); a plain
+//! password is one as well. The [`Identity`](../auth/struct.Identity.html) a client
+//! authenticated as is part of every [`CommandRequest`](struct.CommandRequest.html).
+//!
+//! Every connection is served by its own thread, using the blocking `TcpStream` of the
+//! standard library. The number of connections, failed authentications and idle clients can
+//! be limited with [`Limits`](../limits/struct.Limits.html).
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{client::*, error::*, server::*};
+//! use std::thread;
+//!
+//! // a server which echoes every command back, except for `long`.
+//! let server =
+//!     RconServer::bind("127.0.0.1:0", "mypass",
+//!                      |request: &CommandRequest| match request.command {
+//!                          "long" => "ä".repeat(5000),
+//!                          command => format!("echo: {}", command),
+//!                      })
+//!     .unwrap();
+//! let addr = server.local_addr().unwrap();
+//! thread::spawn(move || server.serve());
+//!
+//! let mut rcon =
+//!     RconClient::open(addr.to_string(), "mypass", None::<String>, None).unwrap();
+//! assert_eq!(rcon.exec("/help").unwrap(), "echo: /help");
+//!
+//! // 10000 bytes do not fit into one packet; the server splits them.
+//! assert_eq!(rcon.exec("long").unwrap(), "ä".repeat(5000));
+//! assert_eq!(rcon.exec("/help").unwrap(), "echo: /help");
+//!
+//! // a wrong password is rejected.
+//! assert!(matches!(RconClient::open(addr.to_string(), "wrong", None::<String>, None),
+//!                  Err(RconError::AuthFailed)));
+//! ```
+//!
+//! ## Protocol
+//!
+//! The server follows the spec: an auth request is answered by an empty
+//! `SERVERDATA_RESPONSE_VALUE`, followed by a `SERVERDATA_AUTH_RESPONSE` carrying the id of the
+//! request, or `-1` if the password is wrong. Commands are only accepted after a successful
+//! authentication; the connection of a client sending commands before is closed.
+//!
+//! Responses larger than a packet allows are split into several `SERVERDATA_RESPONSE_VALUE`
+//! packets with the id of the command, at UTF-8 character boundaries.
+//!
+//! An empty `SERVERDATA_RESPONSE_VALUE` is mirrored and followed by a packet with the
+//! [`RESPONSE_VALUE_MARKER`](../raw_packet/constant.RESPONSE_VALUE_MARKER.html) as body, as
+//! described in the [spec](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#Multiple-packet_Responses);
+//! this is what clients using the `SERVERDATA_RESPONSE_VALUE` trick expect. Every other packet
+//! is answered by an empty `SERVERDATA_RESPONSE_VALUE` with its id.
+
+use super::auth::*;
+use super::limits::*;
+use super::raw_packet::*;
+
+use std::borrow::Cow;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The id of a `SERVERDATA_AUTH_RESPONSE` for a wrong password.
+pub(crate) const AUTH_FAILED_ID: i32 = -1;
+
+/// A command received by the server, as passed to a [`CommandHandler`](trait.CommandHandler.html).
+#[derive(Debug)]
+pub struct CommandRequest<'a> {
+    /// The packet id of the command.
+    pub id: i32,
+    /// The command itself. Invalid UTF-8 is replaced by `U+FFFD`.
+    pub command: &'a str,
+    /// The address of the client.
+    pub peer: SocketAddr,
+    /// The user the client authenticated as.
+    pub identity: &'a Identity,
+}
+
+/// Executes the commands an [`RconServer`](struct.RconServer.html) receives.
+///
+/// Since connections are served concurrently, a handler might be called from several
+/// threads at once. Closures `Fn(&CommandRequest) -> String` are handlers as well.
+pub trait CommandHandler: Send + Sync {
+    /// Executes `request` and returns the response for the client.
+    fn handle(&self, request: &CommandRequest) -> String;
+}
+
+impl<F> CommandHandler for F
+    where F: Fn(&CommandRequest) -> String + Send + Sync {
+    fn handle(&self, request: &CommandRequest) -> String {
+        self(request)
+    }
+}
+
+/// What a connection should do after a packet was handled.
+pub(crate) enum Reply {
+    /// Send the packets and go on.
+    Send(Vec<RawPacket>),
+    /// Send the packets, then close the connection.
+    SendAndClose(Vec<RawPacket>),
+    /// Close the connection.
+    Close,
+}
+
+/// The protocol of one server connection, independent of the I/O.
+pub(crate) struct ServerConnection {
+    peer: SocketAddr,
+    connected_at: Instant,
+    identity: Option<Identity>,
+}
+
+impl ServerConnection {
+    pub(crate) fn new(peer: SocketAddr) -> ServerConnection {
+        ServerConnection { peer, connected_at: Instant::now(), identity: None }
+    }
+
+    /// Until when the next packet has to arrive, if at all.
+    pub(crate) fn deadline(&self, limits: &Limits) -> Option<(Instant, TimeoutKind)> {
+        match self.identity {
+            None =>
+                limits.auth_timeout
+                .map(|timeout| (self.connected_at + timeout, TimeoutKind::Auth)),
+            Some(_) =>
+                limits.idle_timeout
+                .map(|timeout| (Instant::now() + timeout, TimeoutKind::Idle)),
+        }
+    }
+
+    pub(crate) fn handle<A, H>(&mut self,
+                               packet: &RawPacket,
+                               authenticator: &A,
+                               handler: &H,
+                               gate: &Gatekeeper) -> Reply
+        where A: Authenticator + ?Sized,
+              H: CommandHandler + ?Sized {
+        match packet.request_type() {
+            Some(PacketType::RequestAuth) if gate.is_banned(self.peer.ip()) => {
+                // the address got banned while this connection was open.
+                self.identity = None;
+                Reply::SendAndClose(auth_reply(packet.pid, AUTH_FAILED_ID))
+            },
+            Some(PacketType::RequestAuth) => {
+                self.identity = authenticator.authenticate(&packet.pbody);
+                let banned = match &self.identity {
+                    Some(identity) => {
+                        gate.auth_succeeded(self.peer, identity);
+                        false
+                    },
+                    None =>
+                        gate.auth_failed(self.peer),
+                };
+                let auth_id = if self.identity.is_some() { packet.pid } else { AUTH_FAILED_ID };
+
+                let packets = auth_reply(packet.pid, auth_id);
+                if banned {
+                    Reply::SendAndClose(packets)
+                } else {
+                    Reply::Send(packets)
+                }
+            },
+            Some(PacketType::RequestExecCommand) =>
+                match &self.identity {
+                    Some(identity) => {
+                        let command: Cow<str> = packet.body_utf8_lossy();
+                        let request =
+                            CommandRequest { id: packet.pid,
+                                             command: &command,
+                                             peer: self.peer,
+                                             identity };
+                        let output = handler.handle(&request);
+
+                        Reply::Send(response_packets(packet.pid, &output))
+                    },
+                    None =>
+                        Reply::Close,
+                },
+            _ if packet.response_type() == Some(PacketType::ResponseValue)
+                && packet.pbody.is_empty() =>
+                Reply::Send(
+                    vec![empty_response(packet.pid),
+                         RawPacket::new_response_value(packet.pid, RESPONSE_VALUE_MARKER)
+                         .expect("marker packet is valid")]),
+            _ =>
+                Reply::Send(vec![empty_response(packet.pid)]),
+        }
+    }
+}
+
+/// Splits `output` into as many `SERVERDATA_RESPONSE_VALUE` packets with the id `id` as
+/// needed, without splitting any UTF-8 character.
+pub(crate) fn response_packets(id: i32, output: &str) -> Vec<RawPacket> {
+    let mut packets = Vec::new();
+    let mut rest = output;
+
+    loop {
+        let mut end = rest.len().min(MAX_BODY_SIZE as usize);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (body, tail) = rest.split_at(end);
+
+        packets.push(
+            RawPacket::new_response_value(id, body)
+                .expect("body fits into a packet"));
+
+        if tail.is_empty() {
+            return packets;
+        }
+        rest = tail;
+    }
+}
+
+// The reply to the auth request with the id `id`: an empty `SERVERDATA_RESPONSE_VALUE`,
+// followed by the `SERVERDATA_AUTH_RESPONSE` with the id `auth_id`.
+fn auth_reply(id: i32, auth_id: i32) -> Vec<RawPacket> {
+    vec![empty_response(id),
+         RawPacket::new_response_auth(auth_id, "")
+         .expect("empty packet is valid")]
+}
+
+fn empty_response(id: i32) -> RawPacket {
+    RawPacket::new_response_value(id, "")
+        .expect("empty packet is valid")
+}
+
+// How long accepting pauses after an error which is not about a single connection, e.g.
+// running out of file descriptors, instead of failing again right away.
+pub(crate) const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+// An accept error which only concerns the connection being accepted.
+pub(crate) fn is_connection_error(error: &io::Error) -> bool {
+    matches!(error.kind(),
+             io::ErrorKind::ConnectionAborted
+             | io::ErrorKind::ConnectionReset
+             | io::ErrorKind::Interrupted)
+}
+
+/// A blocking RCON server. See the [module documentation](index.html).
+pub struct RconServer<H> {
+    listener: TcpListener,
+    authenticator: Arc<dyn Authenticator>,
+    handler: Arc<H>,
+    limits: Limits,
+    observer: Arc<dyn ServerObserver>,
+}
+
+impl<H: CommandHandler + 'static> RconServer<H> {
+    /// Binds the server to `addr`. Clients need to authenticate against `authenticator`,
+    /// e.g. a password given as `&str`; every command is passed to `handler`.
+    ///
+    /// Binding to port `0` lets the system choose a free port; see
+    /// [`local_addr`](#method.local_addr).
+    pub fn bind<A: ToSocketAddrs,
+                Au: Authenticator + 'static>(addr: A,
+                                             authenticator: Au,
+                                             handler: H) -> io::Result<RconServer<H>> {
+        let listener = TcpListener::bind(addr)?;
+
+        Ok(
+            RconServer {
+                listener,
+                authenticator: Arc::new(authenticator),
+                handler: Arc::new(handler),
+                limits: Limits::default(),
+                observer: Arc::new(()),
+            })
+    }
+
+    /// Sets the limits of the server; without, there are none.
+    pub fn with_limits(mut self, limits: Limits) -> RconServer<H> {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the observer told about connections, authentications, bans and timeouts.
+    pub fn with_observer<O: ServerObserver + 'static>(mut self, observer: O) -> RconServer<H> {
+        self.observer = Arc::new(observer);
+        self
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections and serves each of them in its own thread. Never returns:
+    /// errors accepting a connection are reported to the observer's
+    /// [`accept_failed`](../limits/trait.ServerObserver.html#method.accept_failed) and
+    /// skipped.
+    pub fn serve(self) -> ! {
+        let gate = Arc::new(Gatekeeper::new(self.limits, self.observer));
+
+        loop {
+            let (stream, peer) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    gate.observer.accept_failed(&e);
+                    if !is_connection_error(&e) {
+                        thread::sleep(ACCEPT_BACKOFF);
+                    }
+                    continue;
+                },
+            };
+            // rejected connections are closed by dropping the stream.
+            let permit = match gate.admit(peer) {
+                Some(permit) => permit,
+                None => continue,
+            };
+            let authenticator = Arc::clone(&self.authenticator);
+            let handler = Arc::clone(&self.handler);
+
+            thread::spawn(move || {
+                // a failing connection only ends this connection.
+                let _ = serve_connection(stream, peer, &*authenticator, &*handler, &permit);
+            });
+        }
+    }
+}
+
+fn serve_connection<H: CommandHandler + ?Sized>(mut stream: TcpStream,
+                                                peer: SocketAddr,
+                                                authenticator: &dyn Authenticator,
+                                                handler: &H,
+                                                permit: &Permit) -> io::Result<()> {
+    let gate = permit.gate();
+    let mut connection = ServerConnection::new(peer);
+
+    loop {
+        let deadline = connection.deadline(&gate.limits);
+        let mut reader = DeadlineReader { stream: &stream, deadline: deadline.map(|(deadline, _)| deadline) };
+
+        let packet = match RawPacket::deserialize(&mut reader) {
+            Ok(packet) => packet,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                if let Some((_, kind)) = deadline {
+                    gate.observer.timed_out(peer, kind);
+                }
+                return Err(e);
+            },
+            Err(e) =>
+                return Err(e),
+        };
+
+        match connection.handle(&packet, authenticator, handler, gate) {
+            Reply::Send(packets) =>
+                write_packets(&mut stream, &packets)?,
+            Reply::SendAndClose(packets) =>
+                return write_packets(&mut stream, &packets),
+            Reply::Close =>
+                return Ok(()),
+        }
+    }
+}
+
+// Reads from a stream within a deadline: every read only waits for the time left, so a
+// client sending a packet byte by byte cannot stretch the deadline.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining.is_some_and(|remaining| remaining.is_zero()) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Deadline passed."));
+        }
+        self.stream.set_read_timeout(remaining)?;
+        self.stream.read(buf)
+    }
+}
+
+fn write_packets(stream: &mut TcpStream, packets: &[RawPacket]) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    for packet in packets {
+        packet.serialize_into(&mut buffer);
+    }
+    stream.write_all(&buffer)
+}
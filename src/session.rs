@@ -0,0 +1,573 @@
+//! # Session
+//!
+//! A sans-IO implementation of the client side of the protocol. A [`ClientSession`](struct.ClientSession.html)
+//! does not own any socket: it is fed the bytes received from the server, hands out the bytes
+//! which need to be sent to the server and reports what happened as [`SessionEvent`](enum.SessionEvent.html)s.
+//!
+//! Both [`RconClient`](../client/struct.RconClient.html) and the async client are built on top
+//! of it, so the protocol logic (the auth reply quirks and the control packet scheme to find
+//! the end of multi-packet responses, see the [`client`](../client/index.html) module) exists
+//! only once. It can also be used to drive RCON connections from an own event loop.
+//!
+//! How the end of a response is found is up to a
+//! [`TerminationStrategy`](../termination/trait.TerminationStrategy.html). Strategies based
+//! on a quiet period need the I/O layer to tell the session when the server was silent for
+//! long enough, see [`quiet_period`](struct.ClientSession.html#method.quiet_period).
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{raw_packet::*, session::*};
+//!
+//! // the bytes a server would send, see below.
+//! fn server_bytes(packets: &[RawPacket]) -> Vec<u8> {
+//!     let mut bytes = Vec::new();
+//!     for p in packets { p.serialize(&mut bytes).unwrap(); }
+//!     bytes
+//! }
+//!
+//! let mut session = ClientSession::new(Some("echo")).unwrap();
+//!
+//! // authenticate; the server answers with the double auth reply.
+//! session.authenticate("mypass").unwrap();
+//! let outgoing = session.take_outgoing(); // ... to be written to the server.
+//! assert!(!outgoing.is_empty());
+//!
+//! session.feed(&server_bytes(&[
+//!     RawPacket::new_response_value(0, "").unwrap(),
+//!     RawPacket::new_response_auth(0, "").unwrap(),
+//! ])).unwrap();
+//! assert_eq!(session.poll_event(), Some(SessionEvent::AuthOk));
+//!
+//! // execute a command; the response comes in two packets, followed by the
+//! // response to the control packet. The bytes can be fed in any chunks.
+//! let id = session.exec("status").unwrap();
+//! let _ = session.take_outgoing();
+//!
+//! let bytes = server_bytes(&[
+//!     RawPacket::new_response_value(id, "players: ").unwrap(),
+//!     RawPacket::new_response_value(id, "3").unwrap(),
+//!     RawPacket::new_response_value(-1, "echo").unwrap(),
+//! ]);
+//! let (first, second) = bytes.split_at(17);
+//! session.feed(first).unwrap();
+//! assert_eq!(session.poll_event(), None);
+//! session.feed(second).unwrap();
+//!
+//! assert_eq!(session.poll_event(),
+//!            Some(SessionEvent::ResponseComplete { id, body: b"players: 3".to_vec() }));
+//! ```
+
+use super::raw_packet::*;
+use super::decoder::PacketDecoder;
+use super::error::ProtocolError;
+use super::termination::*;
+use super::probe::*;
+use super::dialect::Dialect;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+pub(crate) const START_ID: i32 = 0; // used as the id for the auth packet
+const FIRST_ID: i32 = 1; // used as the id for the first request, counting up
+
+
+// Some RCON Servers implement a double back response for an auth request. They send first a
+// ResponseValue, then a ResponseAuth. Some servers just send a ResponseAuth.
+// The session allows both ways, by waiting for a second packet if the first one is no
+// ResponseAuth.
+enum AuthCheck {
+    Invalid, NoAuth, Valid
+}
+fn check_auth(packet_id: i32, packet: &RawPacket) -> AuthCheck {
+    if packet.response_type() == Some(PacketType::ResponseAuth) {
+        if packet.pid == packet_id {
+            AuthCheck::Valid
+        } else {
+            AuthCheck::Invalid
+        }
+    } else {
+        AuthCheck::NoAuth
+    }
+}
+
+fn queue(outgoing: &mut Vec<u8>, packet: &RawPacket) {
+    packet.serialize_into(outgoing);
+}
+
+/// The strategy of `ClientSession::new`: the `safe_command`, or the `RESPONSE_VALUE` trick.
+pub(crate) fn default_strategy<C: Into<String>>(safe_command: Option<C>)
+                                                -> Result<Box<dyn TerminationStrategy>, RawPacketCreationError> {
+    Ok(match safe_command {
+        Some(command) => Box::new(SafeCommand::new(command)?),
+        None => Box::new(ResponseValueMirror),
+    })
+}
+
+/// Something which happened on a [`ClientSession`](struct.ClientSession.html) due to the
+/// bytes it was fed.
+#[derive(Debug, Eq, PartialEq)]
+pub enum SessionEvent {
+    /// The server accepted the password.
+    AuthOk,
+    /// The server rejected the password.
+    AuthFailed,
+    /// The full response to the command with the id `id` was received. The body is
+    /// kept as bytes; see [`TextDecoding`](../raw_packet/enum.TextDecoding.html).
+    ResponseComplete { id: i32, body: Vec<u8> },
+    /// The response to the command with the id `id` was received completely, but it
+    /// exceeded the maximum response size and got dropped.
+    ResponseTooLarge { id: i32 },
+    /// Probing the server finished; the session uses the termination strategy of the
+    /// profile from now on.
+    Probed(ServerProfile),
+}
+
+enum State {
+    Unauthenticated,
+    /// Waiting for the auth reply; `replies` counts the packets which were no
+    /// `ResponseAuth` so far.
+    Authenticating { replies: u8 },
+    Ready,
+    /// Collecting the responses of the pending commands, in the order they were sent. The
+    /// termination strategy finds the end of the last one.
+    Responding { responses: Vec<Response> },
+    /// Probing the server; the probe packet of the first of the `candidates` was sent
+    /// with the id `id`, which is the negated id of the request.
+    Probing { id: i32, candidates: VecDeque<Termination>, timeout: Duration, fallback: Termination },
+}
+
+// The response to a pending command; `overflow` is set once the body exceeded the maximum
+// response size.
+struct Response {
+    id: i32,
+    body: Vec<u8>,
+    overflow: bool,
+}
+
+impl Response {
+    fn new(id: i32) -> Response {
+        Response { id, body: Vec::new(), overflow: false }
+    }
+
+    fn append(&mut self, bytes: &[u8], max_response_size: Option<usize>) {
+        if !self.overflow {
+            self.body.extend_from_slice(bytes);
+        }
+        if max_response_size.is_some_and(|max| self.body.len() > max) {
+            // drop what is there, but keep receiving until the end of the response.
+            self.body = Vec::new();
+            self.overflow = true;
+        }
+    }
+
+    fn into_event(self) -> SessionEvent {
+        if self.overflow {
+            SessionEvent::ResponseTooLarge { id: self.id }
+        } else {
+            SessionEvent::ResponseComplete { id: self.id, body: self.body }
+        }
+    }
+}
+
+/// The sans-IO client state machine. See the [module documentation](index.html).
+///
+/// A session handles one request at a time: `exec` must only be called after the
+/// `ResponseComplete` event of the previous command, or after giving it up with
+/// [`cancel`](#method.cancel). A batch of [`exec_many`](#method.exec_many) counts as one
+/// request.
+///
+/// Every request gets an id of its own, counting up. Packets answering an earlier request,
+/// e.g. the rest of a response which was given up or a packet the server sent twice, are
+/// recognized by their id and skipped, so they cannot end up in the response of another
+/// request.
+pub struct ClientSession {
+    state: State,
+    /// Finds the end of the responses.
+    strategy: Box<dyn TerminationStrategy>,
+    /// The id of the last command, to classify packets arriving after its response.
+    last_id: i32,
+    /// The id of the next request.
+    next_id: i32,
+    /// The number of skipped packets of earlier requests.
+    stale_packets: u64,
+    auth_reply: AuthReply,
+    /// The auth reply the server is known to send, if any.
+    expected_auth_reply: Option<AuthReply>,
+    max_command_len: Option<usize>,
+    incoming: PacketDecoder,
+    outgoing: Vec<u8>,
+    events: VecDeque<SessionEvent>,
+    max_response_size: Option<usize>,
+}
+
+impl ClientSession {
+    /// Creates a new session. The `safe_command` works as described in
+    /// [`RconClient::open`](../client/struct.RconClient.html#method.open): if given, it is
+    /// used as the control command, otherwise the `SERVERDATA_RESPONSE_VALUE` trick is used.
+    pub fn new<C: Into<String>>(safe_command: Option<C>) -> Result<ClientSession, RawPacketCreationError> {
+        Ok(ClientSession::with_strategy(default_strategy(safe_command)?))
+    }
+
+    /// Creates a new session, which finds the end of responses with `strategy`.
+    pub fn with_strategy<S: TerminationStrategy + 'static>(strategy: S) -> ClientSession {
+        ClientSession {
+            state: State::Unauthenticated,
+            strategy: Box::new(strategy),
+            last_id: START_ID,
+            next_id: FIRST_ID,
+            stale_packets: 0,
+            auth_reply: AuthReply::Double,
+            expected_auth_reply: None,
+            max_command_len: None,
+            incoming: PacketDecoder::new(),
+            outgoing: Vec::new(),
+            events: VecDeque::new(),
+            max_response_size: None,
+        }
+    }
+
+    /// Creates a new session for a server speaking `dialect`: the session uses the
+    /// termination strategy of the dialect, rejects commands longer than the server accepts
+    /// and accepts packets as large as the server sends. A server of a dialect with a single
+    /// auth reply, which sends anything else than a `SERVERDATA_AUTH_RESPONSE` to the auth
+    /// request, violates the protocol. See the [`dialect`](../dialect/index.html) module.
+    pub fn with_dialect(dialect: Dialect) -> ClientSession {
+        let strategy =
+            dialect.termination().strategy()
+            .expect("dialects have no safe command");
+
+        let mut session = ClientSession::with_strategy(strategy);
+        session.incoming = PacketDecoder::with_max_packet_size(dialect.max_packet_size());
+        session.expected_auth_reply = Some(dialect.auth_reply());
+        session.max_command_len = Some(dialect.max_command_len());
+        session
+    }
+
+    /// Creates a new session for a server probed before: the session uses the termination
+    /// strategy of `profile` and expects its auth reply, as
+    /// [`with_dialect`](#method.with_dialect) does for a dialect. Fails for a safe command
+    /// which does not fit into a packet. See the [`probe`](../probe/index.html) module.
+    pub fn with_profile(profile: &ServerProfile) -> Result<ClientSession, RawPacketCreationError> {
+        let mut session = ClientSession::with_strategy(profile.strategy()?);
+        session.expected_auth_reply = Some(profile.auth_reply);
+        Ok(session)
+    }
+
+    /// Limits the size of a response body. Responses exceeding it are still received
+    /// completely, to keep the session in sync, but reported as `ResponseTooLarge`
+    /// event instead of `ResponseComplete`. `None` (the default) means no limit.
+    pub fn set_max_response_size(&mut self, max_response_size: Option<usize>) {
+        self.max_response_size = max_response_size;
+    }
+
+    /// Prepares the session for a new connection to the same server: it is unauthenticated,
+    /// and buffered bytes and events of the former connection are dropped. The termination
+    /// strategy and the limits are kept, and request ids keep counting.
+    pub fn reset(&mut self) {
+        self.state = State::Unauthenticated;
+        self.incoming.clear();
+        self.outgoing.clear();
+        self.events.clear();
+    }
+
+    /// Queues the auth request with the password `pass`. The outcome is reported as
+    /// either `AuthOk` or `AuthFailed` event.
+    pub fn authenticate<P: Into<String>>(&mut self, pass: P) -> Result<(), RawPacketCreationError> {
+        let auth_packet =
+            RawPacket::new_auth(START_ID, pass.into())?;
+
+        queue(&mut self.outgoing, &auth_packet);
+        self.state = State::Authenticating { replies: 0 };
+        Ok(())
+    }
+
+    /// Queues the command `command` followed by the packets of the termination strategy, if
+    /// any, and returns the id of the command. The response is reported as `ResponseComplete`
+    /// event with this id.
+    ///
+    /// Fails if the command is too long for a packet, or for the server of the dialect of the
+    /// session.
+    pub fn exec<T: Into<String>>(&mut self, command: T) -> Result<i32, RawPacketCreationError> {
+        let ids = self.exec_many([command])?;
+        Ok(ids[0])
+    }
+
+    /// Queues all `commands` at once, each with an id of its own, followed by the packets of
+    /// the termination strategy for the last one only, and returns the ids in order. The
+    /// server answers them in order, so the end of the last response ends all of them; the
+    /// batch takes about one round trip instead of one per command. Every response is
+    /// reported as event with its id, once the last one is complete.
+    ///
+    /// Fails if any command is too long; nothing is queued then. An empty batch queues
+    /// nothing either.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use facio::{raw_packet::*, session::*};
+    ///
+    /// let mut session = ClientSession::new(Some("echo")).unwrap();
+    /// session.authenticate("mypass").unwrap();
+    /// let mut bytes = Vec::new();
+    /// RawPacket::new_response_auth(0, "").unwrap().serialize(&mut bytes).unwrap();
+    /// session.feed(&bytes).unwrap();
+    /// assert_eq!(session.poll_event(), Some(SessionEvent::AuthOk));
+    ///
+    /// let ids = session.exec_many(["status", "list"]).unwrap();
+    /// let _ = session.take_outgoing();
+    ///
+    /// let mut bytes = Vec::new();
+    /// RawPacket::new_response_value(ids[0], "players: 3").unwrap().serialize(&mut bytes).unwrap();
+    /// RawPacket::new_response_value(ids[1], "alice, bob").unwrap().serialize(&mut bytes).unwrap();
+    /// RawPacket::new_response_value(-ids[1], "echo").unwrap().serialize(&mut bytes).unwrap();
+    /// session.feed(&bytes).unwrap();
+    ///
+    /// assert_eq!(session.poll_event(),
+    ///            Some(SessionEvent::ResponseComplete { id: ids[0], body: b"players: 3".to_vec() }));
+    /// assert_eq!(session.poll_event(),
+    ///            Some(SessionEvent::ResponseComplete { id: ids[1], body: b"alice, bob".to_vec() }));
+    /// ```
+    pub fn exec_many<T, I>(&mut self, commands: I) -> Result<Vec<i32>, RawPacketCreationError>
+        where T: Into<String>, I: IntoIterator<Item = T> {
+        let mut packets = Vec::new();
+        for command in commands {
+            let command = command.into();
+            if self.max_command_len.is_some_and(|max| command.len() > max) {
+                return Err(RawPacketCreationError::BodyTooLarge);
+            }
+            // the ids are set below, once all commands are known to fit.
+            packets.push(RawPacket::new_exec(START_ID, command)?);
+        }
+        if packets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut responses = Vec::with_capacity(packets.len());
+        for mut packet in packets {
+            packet.pid = self.allocate_id();
+            queue(&mut self.outgoing, &packet);
+            responses.push(Response::new(packet.pid));
+        }
+        let command_id = responses[responses.len() - 1].id;
+        for control_packet in self.strategy.after_command(command_id) {
+            queue(&mut self.outgoing, &control_packet);
+        }
+
+        self.last_id = command_id;
+        let ids = responses.iter().map(|response| response.id).collect();
+        self.state = State::Responding { responses };
+        Ok(ids)
+    }
+
+    /// Queues the probe packets of the candidates of `probe`, one after the other, to find
+    /// out which termination strategy the server supports. See the
+    /// [`probe`](../probe/index.html) module. The outcome is reported as `Probed` event.
+    ///
+    /// Like `exec`, this must only be called while no request is pending. Packets are
+    /// queued while probing goes on, so the I/O layer writes the outgoing bytes before
+    /// every read.
+    pub fn probe(&mut self, probe: &Probe) -> Result<(), RawPacketCreationError> {
+        let candidates: VecDeque<Termination> = probe.candidates().into();
+        for candidate in &candidates {
+            candidate.probe_packet(-self.next_id)?;
+        }
+
+        self.state = State::Probing {
+            id: -self.allocate_id(),
+            candidates,
+            timeout: probe.timeout,
+            fallback: Termination::QuietPeriod(probe.quiet_period),
+        };
+        self.send_probe();
+        Ok(())
+    }
+
+    /// The shape of the auth reply the server sent; the one of the spec before the `AuthOk`
+    /// event.
+    pub fn auth_reply(&self) -> AuthReply {
+        self.auth_reply
+    }
+
+    /// While a response is pending and the termination strategy ends responses after a
+    /// quiet period, the length of this period. While probing, the time to wait for the
+    /// answer to a probe packet. If the server does not send anything for this long, the
+    /// I/O layer calls [`quiet_period_elapsed`](#method.quiet_period_elapsed).
+    pub fn quiet_period(&self) -> Option<Duration> {
+        match self.state {
+            State::Responding { .. } => self.strategy.quiet_period(),
+            State::Probing { timeout, .. } => Some(timeout),
+            _ => None,
+        }
+    }
+
+    /// Tells the session that the server was silent for the [`quiet_period`](#method.quiet_period),
+    /// which completes the pending response. While probing, the next probe packet is queued.
+    pub fn quiet_period_elapsed(&mut self) {
+        match self.state {
+            State::Responding { .. } =>
+                self.finish_response(),
+            State::Probing { .. } => {
+                let next_id = -self.allocate_id();
+                if let State::Probing { ref mut id, ref mut candidates, .. } = self.state {
+                    *id = next_id;
+                    candidates.pop_front();
+                }
+                self.send_probe();
+            },
+            _ => {},
+        }
+    }
+
+    /// Gives up the pending request, e.g. since the server did not answer in time. Packets
+    /// answering it are skipped when they arrive, so the next request can be sent right
+    /// away. A given up probing keeps the former termination strategy.
+    pub fn cancel(&mut self) {
+        if let State::Responding { .. } | State::Probing { .. } = self.state {
+            self.state = State::Ready;
+        }
+    }
+
+    /// The number of packets skipped so far since they answered an earlier request.
+    pub fn stale_packets(&self) -> u64 {
+        self.stale_packets
+    }
+
+    /// Returns `true` after the `AuthOk` event.
+    pub fn is_authenticated(&self) -> bool {
+        matches!(self.state, State::Ready | State::Responding { .. } | State::Probing { .. })
+    }
+
+    /// Takes the bytes which need to be sent to the server.
+    pub fn take_outgoing(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// Takes the next event, if there is any.
+    pub fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.events.pop_front()
+    }
+
+    /// Feeds bytes received from the server into the session. The bytes do not need to
+    /// form whole packets; incomplete packets are kept until the rest arrives.
+    ///
+    /// Fails if the received data violates the protocol. The session cannot be used
+    /// any further in this case.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), ProtocolError> {
+        self.incoming.feed(bytes);
+
+        while let Some(packet) = self.incoming.next_packet()? {
+            self.handle(packet)?;
+        }
+        Ok(())
+    }
+
+    fn allocate_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id = if id == i32::MAX { FIRST_ID } else { id + 1 };
+        id
+    }
+
+    // Whether `pid` is the id of a request before the pending one, or of a control or probe
+    // packet of such a request.
+    fn is_stale(&self, pid: i32) -> bool {
+        let request = pid.checked_abs().unwrap_or(0);
+        let pending = match self.state {
+            State::Responding { ref responses } => responses.iter().any(|response| response.id == request),
+            State::Probing { id, .. } => -id == request,
+            _ => false,
+        };
+
+        request >= FIRST_ID && request < self.next_id && !pending
+    }
+
+    fn finish_response(&mut self) {
+        if let State::Responding { responses } = std::mem::replace(&mut self.state, State::Ready) {
+            self.events.extend(responses.into_iter().map(Response::into_event));
+        }
+    }
+
+    // Queues the probe packet of the next candidate, or finishes probing with the fallback
+    // if there is none.
+    fn send_probe(&mut self) {
+        if let State::Probing { id, ref candidates, ref fallback, .. } = self.state {
+            match candidates.front() {
+                Some(candidate) => {
+                    let packet =
+                        candidate.probe_packet(id)
+                        .expect("probe packets are checked by `probe`")
+                        .expect("candidates have a probe packet");
+                    queue(&mut self.outgoing, &packet);
+                },
+                None => {
+                    let fallback = fallback.clone();
+                    self.finish_probe(fallback);
+                },
+            }
+        }
+    }
+
+    fn finish_probe(&mut self, termination: Termination) {
+        if let State::Probing { .. } = std::mem::replace(&mut self.state, State::Ready) {
+            self.strategy =
+                termination.strategy()
+                .expect("probe packets are checked by `probe`");
+            self.events.push_back(
+                SessionEvent::Probed(ServerProfile { auth_reply: self.auth_reply, termination }));
+        }
+    }
+
+    fn handle(&mut self, packet: RawPacket) -> Result<(), ProtocolError> {
+        let authenticating = matches!(self.state, State::Unauthenticated | State::Authenticating { .. });
+        if !authenticating && self.is_stale(packet.pid) {
+            self.stale_packets += 1;
+            return Ok(());
+        }
+
+        match self.state {
+            State::Authenticating { replies } => {
+                match check_auth(START_ID, &packet) {
+                    AuthCheck::NoAuth if self.expected_auth_reply == Some(AuthReply::Single) =>
+                        return Err(ProtocolError::NoAuthResponse),
+                    AuthCheck::NoAuth if replies == 0 =>
+                        self.state = State::Authenticating { replies: 1 },
+                    AuthCheck::NoAuth =>
+                        return Err(ProtocolError::NoAuthResponse),
+                    AuthCheck::Invalid => {
+                        self.state = State::Unauthenticated;
+                        self.events.push_back(SessionEvent::AuthFailed);
+                    },
+                    AuthCheck::Valid => {
+                        self.auth_reply = if replies == 0 { AuthReply::Single } else { AuthReply::Double };
+                        self.state = State::Ready;
+                        self.events.push_back(SessionEvent::AuthOk);
+                    },
+                }
+            },
+            State::Responding { ref mut responses } => {
+                // packets of an earlier command of a batch belong to it; the strategy only
+                // deals with the last one.
+                let last = responses.len() - 1;
+                let (index, verdict) = match responses.iter().position(|response| response.id == packet.pid) {
+                    Some(index) if index != last => (index, Verdict::Part),
+                    _ => (last, self.strategy.classify(responses[last].id, &packet)),
+                };
+
+                if let Verdict::Part | Verdict::Last = verdict {
+                    responses[index].append(&packet.pbody, self.max_response_size);
+                }
+                if let Verdict::Last | Verdict::End = verdict {
+                    self.finish_response();
+                }
+            },
+            State::Probing { id, ref mut candidates, .. } if packet.pid == id => {
+                let termination = candidates.pop_front().expect("probing has a candidate");
+                self.finish_probe(termination);
+            },
+            State::Ready if self.strategy.classify(self.last_id, &packet) == Verdict::Ignore => {},
+            State::Unauthenticated | State::Ready | State::Probing { .. } =>
+                return Err(ProtocolError::UnexpectedPacket { pid: packet.pid }),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+//! # Termination
+//!
+//! The protocol has no way to tell the last packet of a multi-packet response. Every client
+//! has to guess, and which guess works depends on the server. A
+//! [`TerminationStrategy`](trait.TerminationStrategy.html) is such a guess; it decides which
+//! packets are sent after a command and which received packet ends the response.
+//!
+//! The built-in strategies:
+//!
+//! - [`SafeCommand`](struct.SafeCommand.html): sends a command with a short answer after
+//!   every command; its answer ends the response.
+//! - [`ResponseValueMirror`](struct.ResponseValueMirror.html): sends an empty
+//!   `SERVERDATA_RESPONSE_VALUE`, which servers following the spec mirror; the marker packet
+//!   which some send after the mirrored one is skipped.
+//! - [`InvalidTypeProbe`](struct.InvalidTypeProbe.html): sends a packet of an unknown type,
+//!   which e.g. Minecraft answers with `Unknown request`.
+//! - [`QuietPeriod`](struct.QuietPeriod.html): sends nothing and ends the response once the
+//!   server was silent for a while.
+//! - [`BodyLength`](struct.BodyLength.html): sends nothing and ends the response with the
+//!   first packet shorter than the maximum fragment size.
+//! - [`SinglePacket`](struct.SinglePacket.html): sends nothing and ends the response with
+//!   its first packet, for servers which never split responses.
+//!
+//! A strategy is selected per connection, e.g. with
+//! [`RconClient::open_with_strategy`](../client/struct.RconClient.html#method.open_with_strategy).
+//!
+//! ## Example
+//!
+//! ```
+//! use facio::{raw_packet::*, session::*, termination::*};
+//!
+//! let mut session = ClientSession::with_strategy(BodyLength::new(4));
+//! session.authenticate("mypass").unwrap();
+//!
+//! let mut reply = Vec::new();
+//! RawPacket::new_response_auth(0, "").unwrap().serialize(&mut reply).unwrap();
+//! session.feed(&reply).unwrap();
+//! assert_eq!(session.poll_event(), Some(SessionEvent::AuthOk));
+//!
+//! // the server splits the response into fragments of 4 bytes; the short one is the last.
+//! let id = session.exec("status").unwrap();
+//! let mut reply = Vec::new();
+//! for fragment in &["play", "ers:", " 3"] {
+//!     RawPacket::new_response_value(id, *fragment).unwrap().serialize(&mut reply).unwrap();
+//! }
+//! session.feed(&reply).unwrap();
+//! assert_eq!(session.poll_event(),
+//!            Some(SessionEvent::ResponseComplete { id, body: b"players: 3".to_vec() }));
+//! ```
+
+use super::raw_packet::*;
+
+use std::time::Duration;
+
+/// What a received packet means for the pending response, as decided by a
+/// [`TerminationStrategy`](trait.TerminationStrategy.html).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Verdict {
+    /// The packet is part of the response; more might follow.
+    Part,
+    /// The packet is the last part of the response.
+    Last,
+    /// The packet ends the response, but is no part of it, e.g. the answer to a control
+    /// packet.
+    End,
+    /// The packet carries no information and is skipped. Such packets are skipped while no
+    /// response is pending as well.
+    Ignore,
+}
+
+/// The id of the control packets sent after the command with the id `command_id`. Command
+/// ids are positive and count up, so control packets have ids of their own as well, and
+/// late answers to them are recognized as such.
+pub fn control_id(command_id: i32) -> i32 {
+    -command_id
+}
+
+/// Detects the end of multi-packet responses. See the [module documentation](index.html).
+///
+/// Packets answering earlier commands are skipped by the session before they get here.
+pub trait TerminationStrategy: Send {
+    /// The packets to send right after the command with the id `command_id`.
+    fn after_command(&mut self, command_id: i32) -> Vec<RawPacket>;
+
+    /// Decides what `packet` means for the response to the command with the id `command_id`.
+    fn classify(&mut self, command_id: i32, packet: &RawPacket) -> Verdict;
+
+    /// If the response is complete once the server was silent for this long. `None` by
+    /// default, i.e. the clients wait for the packet ending the response.
+    fn quiet_period(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl<T: TerminationStrategy + ?Sized> TerminationStrategy for Box<T> {
+    fn after_command(&mut self, command_id: i32) -> Vec<RawPacket> {
+        (**self).after_command(command_id)
+    }
+
+    fn classify(&mut self, command_id: i32, packet: &RawPacket) -> Verdict {
+        (**self).classify(command_id, packet)
+    }
+
+    fn quiet_period(&self) -> Option<Duration> {
+        (**self).quiet_period()
+    }
+}
+
+/// Sends a command, which the server answers with exactly one packet, after every
+/// command. See the [`client`](../client/index.html#safecheck-command) module.
+#[derive(Debug, Clone)]
+pub struct SafeCommand {
+    command: String,
+}
+
+impl SafeCommand {
+    /// Uses `command` as the safe command; fails if it does not fit into a packet.
+    pub fn new<C: Into<String>>(command: C) -> Result<SafeCommand, RawPacketCreationError> {
+        let command = command.into();
+        RawPacket::new_exec(0, command.as_str())?;
+        Ok(SafeCommand { command })
+    }
+}
+
+impl TerminationStrategy for SafeCommand {
+    fn after_command(&mut self, command_id: i32) -> Vec<RawPacket> {
+        vec![RawPacket::new_exec(control_id(command_id), self.command.as_str())
+                 .expect("command fits into a packet")]
+    }
+
+    fn classify(&mut self, command_id: i32, packet: &RawPacket) -> Verdict {
+        if packet.pid == control_id(command_id) { Verdict::End } else { Verdict::Part }
+    }
+}
+
+/// Sends an empty `SERVERDATA_RESPONSE_VALUE` after every command; the mirrored packet ends
+/// the response. The packet with the
+/// [`RESPONSE_VALUE_MARKER`](../raw_packet/constant.RESPONSE_VALUE_MARKER.html) servers send
+/// after the mirrored one, as described in the spec, is skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseValueMirror;
+
+impl TerminationStrategy for ResponseValueMirror {
+    fn after_command(&mut self, command_id: i32) -> Vec<RawPacket> {
+        vec![RawPacket::new_response_value(control_id(command_id), "").expect("empty packet is valid")]
+    }
+
+    fn classify(&mut self, command_id: i32, packet: &RawPacket) -> Verdict {
+        if packet.pid != control_id(command_id) {
+            Verdict::Part
+        } else if packet.pbody == RESPONSE_VALUE_MARKER {
+            Verdict::Ignore
+        } else {
+            Verdict::End
+        }
+    }
+}
+
+/// Sends a packet of an unknown type after every command. Servers answer it with an error
+/// message, e.g. Minecraft with `Unknown request c8`, which ends the response.
+#[derive(Debug, Clone)]
+pub struct InvalidTypeProbe {
+    ptype: i32,
+}
+
+impl InvalidTypeProbe {
+    /// The type of the probe by default, `0xc8`.
+    pub const DEFAULT_TYPE: i32 = 0xc8;
+
+    /// Probes with a packet of the type `ptype`, which must not be one of the spec.
+    pub fn new(ptype: i32) -> InvalidTypeProbe {
+        InvalidTypeProbe { ptype }
+    }
+}
+
+impl Default for InvalidTypeProbe {
+    fn default() -> InvalidTypeProbe {
+        InvalidTypeProbe::new(InvalidTypeProbe::DEFAULT_TYPE)
+    }
+}
+
+impl TerminationStrategy for InvalidTypeProbe {
+    fn after_command(&mut self, command_id: i32) -> Vec<RawPacket> {
+        vec![RawPacket::new(control_id(command_id), self.ptype, "").expect("empty packet is valid")]
+    }
+
+    fn classify(&mut self, command_id: i32, packet: &RawPacket) -> Verdict {
+        if packet.pid == control_id(command_id) { Verdict::End } else { Verdict::Part }
+    }
+}
+
+/// Ends the response once the server did not send anything for a while. Works with every
+/// server, at the price of the waiting time for every command; a slow server might have
+/// its response cut.
+///
+/// # Example
+///
+/// ```
+/// use facio::{client::*, server::*, termination::*};
+/// use std::{thread, time::Duration};
+///
+/// let server =
+///     RconServer::bind("127.0.0.1:0", "mypass", |_: &CommandRequest| "x".repeat(10000))
+///     .unwrap();
+/// let addr = server.local_addr().unwrap();
+/// thread::spawn(move || server.serve());
+///
+/// let mut rcon =
+///     RconClient::open_with_strategy(addr.to_string(), "mypass",
+///                                    QuietPeriod::new(Duration::from_millis(100)), None)
+///     .unwrap();
+/// assert_eq!(rcon.exec("long").unwrap().len(), 10000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct QuietPeriod {
+    period: Duration,
+}
+
+impl QuietPeriod {
+    /// Ends responses after `period` of silence; at least a millisecond.
+    pub fn new(period: Duration) -> QuietPeriod {
+        QuietPeriod { period: period.max(Duration::from_millis(1)) }
+    }
+}
+
+impl TerminationStrategy for QuietPeriod {
+    fn after_command(&mut self, _command_id: i32) -> Vec<RawPacket> {
+        Vec::new()
+    }
+
+    fn classify(&mut self, _command_id: i32, _packet: &RawPacket) -> Verdict {
+        Verdict::Part
+    }
+
+    fn quiet_period(&self) -> Option<Duration> {
+        Some(self.period)
+    }
+}
+
+/// Ends the response with the first packet whose body is shorter than the fragment size the
+/// server splits responses at. Needs no round trip, but waits forever for a response whose
+/// length is a multiple of the fragment size, unless combined with a quiet period.
+#[derive(Debug, Clone)]
+pub struct BodyLength {
+    fragment_size: usize,
+    quiet_period: Option<Duration>,
+}
+
+impl BodyLength {
+    /// Assumes that the server splits responses into fragments of `fragment_size` bytes; at
+    /// least one, since no body is shorter than zero bytes.
+    pub fn new(fragment_size: usize) -> BodyLength {
+        BodyLength { fragment_size: fragment_size.max(1), quiet_period: None }
+    }
+
+    /// Also ends the response after `period` of silence, see
+    /// [`QuietPeriod`](struct.QuietPeriod.html).
+    pub fn with_quiet_period(mut self, period: Duration) -> BodyLength {
+        self.quiet_period = Some(period.max(Duration::from_millis(1)));
+        self
+    }
+}
+
+impl Default for BodyLength {
+    /// Assumes fragments of the maximum body size.
+    fn default() -> BodyLength {
+        BodyLength::new(MAX_BODY_SIZE as usize)
+    }
+}
+
+impl TerminationStrategy for BodyLength {
+    fn after_command(&mut self, _command_id: i32) -> Vec<RawPacket> {
+        Vec::new()
+    }
+
+    fn classify(&mut self, _command_id: i32, packet: &RawPacket) -> Verdict {
+        if packet.pbody.len() < self.fragment_size { Verdict::Last } else { Verdict::Part }
+    }
+
+    fn quiet_period(&self) -> Option<Duration> {
+        self.quiet_period
+    }
+}
+
+/// Ends the response with its first packet. Needs no round trip and no waiting, but only
+/// works with servers which never split responses, like Factorio or Palworld.
+#[derive(Debug, Clone, Default)]
+pub struct SinglePacket;
+
+impl TerminationStrategy for SinglePacket {
+    fn after_command(&mut self, _command_id: i32) -> Vec<RawPacket> {
+        Vec::new()
+    }
+
+    fn classify(&mut self, _command_id: i32, _packet: &RawPacket) -> Verdict {
+        Verdict::Last
+    }
+}